@@ -1,62 +1,245 @@
-use HalfScramble::cube::{Cube, Piece};
-use HalfScramble::pdb::build_pdb;
+use HalfScramble::index::{PieceKind, PieceSubset, SymmetricSubset};
+use HalfScramble::pdb::{
+    build_pdb, pdb_specs, reduce_pdb, save_pdb, save_reduced_pdb, EdgeTables, PDBSpec, PDB,
+};
+use HalfScramble::pdb_set::resolve_pdb_dir;
 
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Instant;
 
 use std::env;
+use std::thread;
 
-fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
+const USAGE: &str = "\
+Usage: build_pdb <TABLE|all> [PDB_DIR] [OPTIONS]
 
-    let pdb_num: i32 = args[1].trim().parse().expect("Not a number");
+Arguments:
+  TABLE     Which table to build: 1 (corners), 2 (edges #1), 3 (edges #2)
+  all       Build every table the solver needs, skipping the ones that are already valid
+  PDB_DIR   Where to save the tables [default: $HALFSCRAMBLE_PDB_DIR, ./data or
+            $XDG_DATA_HOME/HalfScramble]
 
-    std::fs::create_dir_all("data")?; // create data dir if missing
+Options:
+  -j, --threads <N>   Number of threads to build with [default: number of cores]
+  -e, --edges <N>     Edges per edge table: 6, 7 or 8 [default: 6]
+                      7-edge tables are ~255 MB each, 8-edge tables are ~2.6 GB each
+                      and need another ~640 MB while building
+  -s, --symmetry      Only store one entry per symmetry class, which makes the corner table
+                      ~3x smaller, the 8-edge tables ~2.5x smaller and the 6- and 7-edge
+                      tables ~1.3x smaller, but lookups slower
+                      (tables with no usable symmetries are stored in full)
+  -f, --force         Rebuild tables even if they already exist and are valid
+  -h, --help          Print this message";
 
-    let path: &Path;
-    let range: std::ops::Range<usize>;
-    let selector: fn(&Cube) -> &[Piece];
-    let orientation_base: usize;
+// What happened to each table
+enum Outcome {
+    Built { bytes: usize, seconds: f64 },
+    Skipped,
+    Failed(std::io::Error),
+}
 
-    match pdb_num {
-        1 => {
-            path = Path::new("data/corner_pdb.bin");
-            range = 0..8;
-            selector = |pdb| &pdb.corners;
-            orientation_base = 3;
-        }
-        2 => {
-            path = Path::new("data/edge_pdb_1.bin");
-            range = 0..8;
-            selector = |pdb| &pdb.edges;
-            orientation_base = 2;
-        }
-        3 => {
-            path = Path::new("data/edge_pdb_2.bin");
-            range = 4..12;
-            selector = |pdb| &pdb.edges;
-            orientation_base = 2;
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut tables: Option<Vec<usize>> = None;
+    let mut edges = EdgeTables::Six;
+    let mut dir: Option<PathBuf> = None;
+    let mut threads: usize = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut force = false;
+    let mut symmetry = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-j" | "--threads" => {
+                threads = iter
+                    .next()
+                    .and_then(|n| n.trim().parse().ok())
+                    .filter(|&n| n > 0)
+                    .unwrap_or_else(|| usage_error("'--threads' needs a positive number"));
+            }
+            "-e" | "--edges" => {
+                edges = iter
+                    .next()
+                    .and_then(|n| n.trim().parse().ok())
+                    .and_then(EdgeTables::from_size)
+                    .unwrap_or_else(|| usage_error("'--edges' needs to be 6, 7 or 8"));
+            }
+            "-s" | "--symmetry" => symmetry = true,
+            "-f" | "--force" => force = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "all" if tables.is_none() => tables = Some((0..3).collect()),
+            _ if tables.is_none() => {
+                let num: usize = arg
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|&n| (1..=3).contains(&n))
+                    .unwrap_or_else(|| usage_error(&format!("'{}' is not a valid table", arg)));
+                tables = Some(vec![num - 1]);
+            }
+            _ if dir.is_none() && !arg.starts_with('-') => dir = Some(PathBuf::from(arg)),
+            _ => usage_error(&format!("unknown argument '{}'", arg)),
         }
+    }
+
+    let tables = tables.unwrap_or_else(|| usage_error("no table given"));
+    let specs = pdb_specs(edges);
+
+    // a single table is always rebuilt, 'all' only builds what is missing or invalid
+    let skip_valid = tables.len() > 1 && !force;
 
-        _ => {
-            println!("Invalid PDB number");
-            return Ok(());
+    let dir = resolve_pdb_dir(dir.as_deref());
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        eprintln!("Error: Could not create '{}': {}", dir.display(), e);
+        process::exit(1);
+    }
+
+    let outcomes: Vec<Outcome> = tables
+        .iter()
+        .map(|&i| {
+            let spec = &specs[i];
+            let path = dir.join(spec.file_name);
+
+            // only reduce the tables that have a symmetry other than the identity
+            let reduce = symmetry && num_symmetries(spec) > 1;
+
+            // the solver doesn't check the checksum of mapped tables, so this is where
+            //  a corrupt table gets noticed (and replaced)
+            if skip_valid && path.is_file() {
+                match check(spec, &path, reduce) {
+                    Ok(()) => {
+                        println!(
+                            "{} is already valid at {}, skipping",
+                            spec.name,
+                            path.display()
+                        );
+                        return Outcome::Skipped;
+                    }
+                    Err(e) => println!(
+                        "{} at {} is invalid ({}), rebuilding it",
+                        spec.name,
+                        path.display(),
+                        e
+                    ),
+                }
+            }
+
+            build(spec, &path, threads, reduce)
+        })
+        .collect();
+
+    // only bother with a summary if there was more than one table
+    if tables.len() > 1 {
+        println!("\nSummary ({}):", dir.display());
+        for (&i, outcome) in tables.iter().zip(outcomes.iter()) {
+            let name = specs[i].name;
+            match outcome {
+                Outcome::Built { bytes, seconds } => {
+                    println!("  {:<14} built ({} bytes in {:.1}s)", name, bytes, seconds)
+                }
+                Outcome::Skipped => println!("  {:<14} skipped (already valid)", name),
+                Outcome::Failed(e) => println!("  {:<14} FAILED: {}", name, e),
+            }
         }
     }
 
-    println!("Generating PDB...");
+    if outcomes.iter().any(|o| matches!(o, Outcome::Failed(_))) {
+        process::exit(1);
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("Error: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+// Check that the file has the right header, passes the checksum
+//  and is reduced (or not) like it was asked for, returns what is wrong with it if not
+fn check(spec: &PDBSpec, path: &Path, reduce: bool) -> Result<(), String> {
+    let pdb = PDB::new(
+        path,
+        spec.range.clone(),
+        spec.selector,
+        spec.orientation_base,
+    )
+    .map_err(|e| e.to_string())?;
+
+    match (pdb.is_reduced(), reduce) {
+        (true, false) => Err(String::from("it is symmetry reduced")),
+        (false, true) => Err(String::from("it is not symmetry reduced")),
+        _ => Ok(()),
+    }
+}
+
+// the number of symmetries that can be used to reduce a table
+fn num_symmetries(spec: &PDBSpec) -> usize {
+    let kind = PieceKind::of(spec.selector);
+    SymmetricSubset::new(PieceSubset::new(kind, spec.range.clone())).num_symmetries()
+}
+
+fn build(spec: &PDBSpec, path: &Path, threads: usize, reduce: bool) -> Outcome {
+    println!("Generating {} with {} threads...", spec.name, threads);
+    let start = Instant::now();
 
     // generate the PDB
-    let pdb = build_pdb(range, selector, orientation_base);
+    let pdb = match build_pdb(
+        spec.range.clone(),
+        spec.selector,
+        spec.orientation_base,
+        threads,
+    ) {
+        Ok(pdb) => pdb,
+        Err(e) => {
+            eprintln!("Error: Could not build {}: {}", spec.name, e);
+            return Outcome::Failed(e.into());
+        }
+    };
 
-    println!("PDB Generated; Initiating Save");
+    let pdb = if reduce {
+        println!(
+            "PDB Generated; Reducing by {} symmetries",
+            num_symmetries(spec)
+        );
+        reduce_pdb(
+            &pdb,
+            spec.range.clone(),
+            spec.selector,
+            spec.orientation_base,
+            threads,
+        )
+    } else {
+        pdb
+    };
+
+    println!("Initiating Save");
 
     // Save it in the project directory
-    let mut file = File::create(path)?;
-    file.write_all(&pdb)?;
+    let save = if reduce { save_reduced_pdb } else { save_pdb };
+    if let Err(e) = save(
+        path,
+        &pdb,
+        spec.range.clone(),
+        spec.selector,
+        spec.orientation_base,
+    ) {
+        eprintln!(
+            "Error: Could not save {} to {}: {}",
+            spec.name,
+            path.display(),
+            e
+        );
+        return Outcome::Failed(e);
+    }
 
     println!("Saved PDB to {} ({} bytes)", path.display(), pdb.len());
 
-    Ok(())
+    Outcome::Built {
+        bytes: pdb.len(),
+        seconds: start.elapsed().as_secs_f64(),
+    }
 }
@@ -0,0 +1,407 @@
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use HalfScramble::cube::Move;
+use HalfScramble::notation::parse_path;
+use HalfScramble::pdb::EdgeTables;
+use HalfScramble::solver::{LengthPolicy, MAX_PATH_LENGTH};
+
+pub const USAGE: &str = "\
+Usage: HalfScramble [OPTIONS]
+
+With no options, scrambles are read interactively from stdin.
+
+Options:
+  -n, --length <N>         Length of the random scrambles to generate
+  -s, --scramble <MOVES>   Solve this scramble instead of a random one (e.g. \"R U R' U'\")
+  -c, --count <N>          Number of scrambles to generate [default: 1]
+  -l, --solution-length <POLICY>
+                           How long the alternate scramble has to be compared to the original:
+                           exact, at-least, optimal, or a range MIN-MAX [default: at-least]
+      --no-shared-states   The alternate scramble can't pass through any state the original does
+      --min-edit-distance <N>
+                           The alternate scramble has to differ from the original by at least
+                           N moves added, removed or changed [default: 0]
+      --no-shared-suffix <K>
+                           The alternate scramble can't start with the same K moves as the
+                           original [default: 0, off]
+  -r, --random-alternate   Pick the alternate scramble at random from all the ones of the same
+                           length (up to 1000), instead of the first one found
+  -t, --timeout <SECS>     Give up looking for an alternate scramble after this long
+                           [default: 300, 0 for no limit]
+      --max-nodes <N>      Give up looking for an alternate scramble after visiting N cubes
+  -j, --threads <N>        Number of threads to search with [default: number of cores]
+      --stats              Print what the search did on every iteration to stderr
+      --seed <SEED>        Seed for the random scrambles, the same seed gives the same scrambles
+  -p, --pdb-dir <DIR>      Directory containing the PDBs [default: $HALFSCRAMBLE_PDB_DIR,
+                           ./data or $XDG_DATA_HOME/HalfScramble]
+      --no-mmap            Read the PDBs into memory instead of memory mapping them
+      --edges <N>          Edges per edge PDB: 6, 7 or 8 [default: 6]
+                           (7 needs ~510 MB of tables, 8 needs ~5.1 GB)
+  -f, --format <FORMAT>    Output format for batch mode: text, json [default: text]
+  -b, --batch              Print the scrambles and solutions without prompting, then exit
+  -h, --help               Print this message";
+
+// How long the alternate scramble has to be, relative to the original scramble
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SolutionLength {
+    Exact,
+    AtLeast,
+    Optimal,
+    // absolute, whatever the original length is
+    Range(i32, i32),
+}
+
+impl SolutionLength {
+    pub fn policy(self, scramble_len: i32) -> LengthPolicy {
+        match self {
+            SolutionLength::Exact => LengthPolicy::Exact(scramble_len),
+            SolutionLength::AtLeast => LengthPolicy::AtLeast(scramble_len),
+            SolutionLength::Optimal => LengthPolicy::Optimal,
+            SolutionLength::Range(min, max) => LengthPolicy::Range(min, max),
+        }
+    }
+}
+
+// the alternate has to be at least as long as the scramble (unless --solution-length says otherwise),
+//  and the solver doesn't search past MAX_PATH_LENGTH
+pub const MAX_SCRAMBLE_LENGTH: i32 = MAX_PATH_LENGTH;
+
+// so a long scramble can't hang the game
+//  long enough for a 12 move scramble on one core with the 6-edge tables (see the README)
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    Text,
+    // one JSON object per line
+    Json,
+}
+
+pub struct Options {
+    pub length: Option<i32>,
+    pub scramble: Option<Vec<Move>>,
+    pub count: usize,
+    pub solution_length: SolutionLength,
+    pub random_alternate: bool,
+    pub no_shared_states: bool,
+    pub min_edit_distance: usize,
+    pub no_shared_suffix: usize,
+    pub timeout: Option<Duration>,
+    pub max_nodes: Option<u64>,
+    pub threads: usize,
+    pub stats: bool,
+    pub seed: Option<u64>,
+    pub pdb_dir: Option<PathBuf>,
+    pub mmap: bool,
+    pub edges: EdgeTables,
+    pub format: Format,
+    pub batch: bool,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            length: None,
+            scramble: None,
+            count: 1,
+            solution_length: SolutionLength::AtLeast,
+            random_alternate: false,
+            no_shared_states: false,
+            min_edit_distance: 0,
+            no_shared_suffix: 0,
+            timeout: Some(DEFAULT_TIMEOUT),
+            max_nodes: None,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            stats: false,
+            seed: None,
+            pdb_dir: None,
+            mmap: true,
+            edges: EdgeTables::Six,
+            format: Format::Text,
+            batch: false,
+            help: false,
+        }
+    }
+}
+
+// The error for a scramble longer than MAX_SCRAMBLE_LENGTH
+pub fn scramble_too_long() -> String {
+    format!("scramble length can be at most {}", MAX_SCRAMBLE_LENGTH)
+}
+
+// Parse the command line arguments (without the program name)
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        // allow both "--flag value" and "--flag=value"
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        // grabs the value for flags that need one
+        let mut value = || -> Result<String, String> {
+            match &inline_value {
+                Some(value) => Ok(value.clone()),
+                None => iter
+                    .next()
+                    .cloned()
+                    .ok_or(format!("'{}' requires a value", flag)),
+            }
+        };
+
+        match flag {
+            "-n" | "--length" => {
+                let value = value()?;
+                let length: i32 = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("'{}' is not a valid scramble length", value))?;
+                if length < 1 {
+                    return Err(String::from("scramble length must be at least 1"));
+                }
+                if length > MAX_SCRAMBLE_LENGTH {
+                    return Err(scramble_too_long());
+                }
+                options.length = Some(length);
+            }
+            "-s" | "--scramble" => {
+                let value = value()?;
+                let scramble =
+                    parse_path(&value).map_err(|e| format!("could not read scramble: {}", e))?;
+                if scramble.is_empty() {
+                    return Err(String::from("scramble must contain at least one move"));
+                }
+                if scramble.len() > MAX_SCRAMBLE_LENGTH as usize {
+                    return Err(scramble_too_long());
+                }
+                options.scramble = Some(scramble);
+            }
+            "-c" | "--count" => {
+                let value = value()?;
+                options.count = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("'{}' is not a valid count", value))?;
+            }
+            "-l" | "--solution-length" => {
+                options.solution_length = parse_solution_length(&value()?)?;
+            }
+            "-r" | "--random-alternate" => options.random_alternate = true,
+            "--no-shared-states" => options.no_shared_states = true,
+            "--min-edit-distance" => {
+                let value = value()?;
+                options.min_edit_distance = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("'{}' is not a valid edit distance", value))?;
+            }
+            "--no-shared-suffix" => {
+                let value = value()?;
+                options.no_shared_suffix = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("'{}' is not a valid suffix length", value))?;
+            }
+            "-t" | "--timeout" => {
+                let value = value()?;
+                // try_from_secs_f64 rejects negative, infinite and too large values
+                let timeout = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .and_then(|secs: f64| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or(format!("'{}' is not a valid timeout", value))?;
+                options.timeout = (!timeout.is_zero()).then_some(timeout);
+            }
+            "--max-nodes" => {
+                let value = value()?;
+                options.max_nodes = Some(
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("'{}' is not a valid node count", value))?,
+                );
+            }
+            "-j" | "--threads" => {
+                let value = value()?;
+                options.threads = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|&threads: &usize| threads > 0)
+                    .ok_or(format!("'{}' is not a valid number of threads", value))?;
+            }
+            "--stats" => options.stats = true,
+            "--seed" => {
+                let value = value()?;
+                options.seed = Some(
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("'{}' is not a valid seed", value))?,
+                );
+            }
+            "-p" | "--pdb-dir" => {
+                options.pdb_dir = Some(PathBuf::from(value()?));
+            }
+            "--no-mmap" => options.mmap = false,
+            "--edges" => {
+                let value = value()?;
+                options.edges = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .and_then(EdgeTables::from_size)
+                    .ok_or(format!(
+                        "'{}' is not a valid edge table size (6, 7 or 8)",
+                        value
+                    ))?;
+            }
+            "-f" | "--format" => {
+                options.format = match value()?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    other => return Err(format!("unknown format '{}'", other)),
+                };
+            }
+            "-b" | "--batch" => options.batch = true,
+            "-h" | "--help" => options.help = true,
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+
+    if options.length.is_some() && options.scramble.is_some() {
+        return Err(String::from(
+            "'--length' and '--scramble' cannot be used together",
+        ));
+    }
+
+    if options.batch && options.length.is_none() && options.scramble.is_none() {
+        return Err(String::from(
+            "'--batch' requires '--length' or '--scramble'",
+        ));
+    }
+
+    Ok(options)
+}
+
+// "exact", "at-least", "optimal" or a range like "10-14"
+fn parse_solution_length(value: &str) -> Result<SolutionLength, String> {
+    match value.trim() {
+        "exact" => Ok(SolutionLength::Exact),
+        "at-least" => Ok(SolutionLength::AtLeast),
+        "optimal" => Ok(SolutionLength::Optimal),
+        range => {
+            let invalid = || format!("'{}' is not a valid solution length", value);
+
+            let (min, max) = range.split_once('-').ok_or_else(invalid)?;
+            let min: i32 = min.trim().parse().map_err(|_| invalid())?;
+            let max: i32 = max.trim().parse().map_err(|_| invalid())?;
+
+            if min > max {
+                return Err(format!("solution length range '{}' is empty", value));
+            }
+            Ok(SolutionLength::Range(min, max))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        parse_args(&args)
+    }
+
+    fn parse_err(args: &str) -> String {
+        match parse(args) {
+            Ok(_) => panic!("'{}' should not parse", args),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn accepts_inline_values() {
+        let options = parse("--length=12 --count=3 --timeout=2.5 --solution-length=10-14").unwrap();
+        assert_eq!(options.length, Some(12));
+        assert_eq!(options.count, 3);
+        assert_eq!(options.timeout, Some(Duration::from_secs_f64(2.5)));
+        assert_eq!(options.solution_length, SolutionLength::Range(10, 14));
+
+        let options = parse("-n 12 --count 3").unwrap();
+        assert_eq!(options.length, Some(12));
+        assert_eq!(options.count, 3);
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        assert_eq!(parse_err("--length"), "'--length' requires a value");
+        assert_eq!(parse_err("-n 5 --seed"), "'--seed' requires a value");
+    }
+
+    #[test]
+    fn rejects_scrambles_longer_than_the_solver_searches() {
+        assert_eq!(parse_err("-n 200000"), scramble_too_long());
+        assert_eq!(
+            parse(&format!("-n {}", MAX_SCRAMBLE_LENGTH))
+                .unwrap()
+                .length,
+            Some(MAX_SCRAMBLE_LENGTH)
+        );
+
+        let scramble = "R U ".repeat(MAX_SCRAMBLE_LENGTH as usize / 2 + 1);
+        let args = [String::from("--scramble"), scramble];
+        assert_eq!(parse_args(&args).err(), Some(scramble_too_long()));
+    }
+
+    #[test]
+    fn rejects_length_with_scramble() {
+        assert_eq!(
+            parse_err("--length 5 --scramble=R"),
+            "'--length' and '--scramble' cannot be used together"
+        );
+    }
+
+    #[test]
+    fn batch_needs_a_scramble_source() {
+        assert_eq!(
+            parse_err("--batch"),
+            "'--batch' requires '--length' or '--scramble'"
+        );
+        assert!(parse("--batch --length 5").is_ok());
+        assert!(parse("--batch --scramble=R").is_ok());
+    }
+
+    #[test]
+    fn rejects_empty_solution_length_ranges() {
+        assert_eq!(
+            parse_err("--solution-length 14-10"),
+            "solution length range '14-10' is empty"
+        );
+        assert_eq!(
+            parse("--solution-length 12-12").unwrap().solution_length,
+            SolutionLength::Range(12, 12)
+        );
+    }
+
+    #[test]
+    fn zero_timeout_is_no_limit() {
+        assert_eq!(parse("--timeout 0").unwrap().timeout, None);
+        assert_eq!(parse("").unwrap().timeout, Some(DEFAULT_TIMEOUT));
+        assert_eq!(parse_err("--timeout -1"), "'-1' is not a valid timeout");
+        assert_eq!(
+            parse_err("--timeout 1e300"),
+            "'1e300' is not a valid timeout"
+        );
+        assert_eq!(parse_err("--timeout inf"), "'inf' is not a valid timeout");
+    }
+}
@@ -1,4 +1,8 @@
 use std::array;
+use std::fmt;
+use std::sync::LazyLock;
+
+use crate::index::PieceKind;
 
 // Move notation will be used to describe the sides of the cube:
 //  U -> up/top     |    D -> down/bottom
@@ -20,6 +24,8 @@
 const EDGE_COLORS: [&str; 12] = [
     "WG", "WR", "WB", "WO", "GR", "BR", "BO", "GO", "YG", "YR", "YB", "YO",
 ];
+// The color of each center, in the same order as the Face enum
+const CENTER_COLORS: [char; 6] = ['W', 'R', 'G', 'O', 'B', 'Y'];
 
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -32,13 +38,89 @@ pub enum Face {
     D,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Move {
     pub face: Face,
     pub coeff: i8,
 }
 
-#[derive(Clone, Copy, Debug)]
+// array of all possible moves
+pub const ALL_MOVES: [Move; 18] = [
+    Move {
+        face: Face::U,
+        coeff: -1,
+    },
+    Move {
+        face: Face::U,
+        coeff: 1,
+    },
+    Move {
+        face: Face::U,
+        coeff: 2,
+    },
+    Move {
+        face: Face::F,
+        coeff: -1,
+    },
+    Move {
+        face: Face::F,
+        coeff: 1,
+    },
+    Move {
+        face: Face::F,
+        coeff: 2,
+    },
+    Move {
+        face: Face::R,
+        coeff: -1,
+    },
+    Move {
+        face: Face::R,
+        coeff: 1,
+    },
+    Move {
+        face: Face::R,
+        coeff: 2,
+    },
+    Move {
+        face: Face::L,
+        coeff: -1,
+    },
+    Move {
+        face: Face::L,
+        coeff: 1,
+    },
+    Move {
+        face: Face::L,
+        coeff: 2,
+    },
+    Move {
+        face: Face::B,
+        coeff: -1,
+    },
+    Move {
+        face: Face::B,
+        coeff: 1,
+    },
+    Move {
+        face: Face::B,
+        coeff: 2,
+    },
+    Move {
+        face: Face::D,
+        coeff: -1,
+    },
+    Move {
+        face: Face::D,
+        coeff: 1,
+    },
+    Move {
+        face: Face::D,
+        coeff: 2,
+    },
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Piece {
     pub pos: i32,
     pub ori: i32,
@@ -46,7 +128,7 @@ pub struct Piece {
     // Corners will have 8 possible positions and 3 possible orientations (Total : 3*8=24)
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Cube {
     // 2 arrays of pieces
     pub corners: [Piece; 8],
@@ -96,71 +178,18 @@ pub fn print(self) {
     }
 
     pub fn make_move(&mut self, mv: Move) {
-        // Get the cycles from the move tables
-        // if coeff is negative, flip the cycle around
-        let corner_pos_cycle: [u8; 4] = {
-            if mv.coeff == -1 {
-                let mut tmp = CORNER_MOVE_TABLE[mv.face as usize];
-                tmp.reverse();
-                tmp
-            } else {
-                CORNER_MOVE_TABLE[mv.face as usize]
-            }
-        };
-
-        let edge_pos_cycle: [u8; 4] = {
-            if mv.coeff == -1 {
-                let mut tmp = EDGE_MOVE_TABLE[mv.face as usize];
-                tmp.reverse();
-                tmp
-            } else {
-                EDGE_MOVE_TABLE[mv.face as usize]
-            }
-        };
+        // every piece just looks up where it goes (see MoveTables)
+        let tables = &*MOVE_TABLES;
+        let m = move_index(mv);
+        let corners = &tables.corners[m];
+        let edges = &tables.edges[m];
 
-        // if double rotation, there are no orientation changes
-        //  so we can just end after these 4 cycles
-        if mv.coeff.abs() == 2 {
-            cycle_pieces(&mut self.corners, &corner_pos_cycle);
-            cycle_pieces(&mut self.corners, &corner_pos_cycle);
-            cycle_pieces(&mut self.edges, &edge_pos_cycle);
-            cycle_pieces(&mut self.edges, &edge_pos_cycle);
-            return;
+        for piece in self.corners.iter_mut() {
+            *piece = corners[(piece.pos * 3 + piece.ori) as usize];
         }
 
-        // Corners
-        {
-            // cycle pieces
-            let corner_cycle = cycle_pieces(&mut self.corners, &corner_pos_cycle);
-
-            // orient pieces
-            for i in 0..4 {
-                // Different moves have differnt rotation profiles
-                let rotation: i32;
-                match mv.face {
-                    Face::F | Face::L => rotation = get_rotation(mv.coeff, i),
-                    Face::B | Face::R => rotation = get_rotation(-mv.coeff, i),
-                    Face::U | Face::D => rotation = 0,
-                }
-
-                // add rotation
-                self.corners[corner_cycle[i]].ori =
-                    (self.corners[corner_cycle[i]].ori + rotation) % 3;
-            }
-        }
-
-        // Edges
-        {
-            // cycle pieces
-            let edge_cycle = cycle_pieces(&mut self.edges, &edge_pos_cycle);
-
-            // orient pieces (Only changes on F or B moves)
-
-            if mv.face == Face::F || mv.face == Face::B {
-                for i in 0..4 {
-                    self.edges[edge_cycle[i]].ori = (self.edges[edge_cycle[i]].ori + 1) % 2;
-                }
-            }
+        for piece in self.edges.iter_mut() {
+            *piece = edges[(piece.pos * 2 + piece.ori) as usize];
         }
     }
 
@@ -183,6 +212,379 @@ pub fn is_solved(&self) -> bool {
 
         return true;
     }
+
+    // Build a cube from the state of every piece (e.g. from another program)
+    //  fails if it can't be reached by turning the faces (see validate)
+    pub fn from_pieces(corners: [Piece; 8], edges: [Piece; 12]) -> Result<Cube, CubeError> {
+        let cube = Cube { corners, edges };
+        cube.validate()?;
+        Ok(cube)
+    }
+
+    // The cube as a facelet string (see Facelet strings below)
+    pub fn to_facelets(&self) -> String {
+        let state = fill_state(self);
+
+        let mut facelets = String::with_capacity(54);
+        for face in FACELET_FACES {
+            for color in state[face as usize].iter().flatten() {
+                facelets.push(FACE_LETTERS[color_face(*color)]);
+            }
+        }
+
+        facelets
+    }
+
+    // Read a cube from a facelet string (see Facelet strings below)
+    //  whitespace is ignored, so the faces can be split up
+    pub fn from_facelets(facelets: &str) -> Result<Cube, FaceletError> {
+        let stickers: Vec<char> = facelets.chars().filter(|c| !c.is_whitespace()).collect();
+        if stickers.len() != 54 {
+            return Err(FaceletError::WrongLength(stickers.len()));
+        }
+
+        // whatever the centers are written as are the colors of their faces
+        let centers: [char; 6] = array::from_fn(|i| stickers[i * 9 + 4]);
+        for (i, center) in centers.iter().enumerate() {
+            if centers[..i].contains(center) {
+                return Err(FaceletError::DuplicateCenter(*center));
+            }
+        }
+
+        // the color of every sticker, as the colors used in CORNER_COLORS / EDGE_COLORS
+        let mut colors = [' '; 54];
+        for (sticker, c) in stickers.iter().enumerate() {
+            let i = centers
+                .iter()
+                .position(|center| center == c)
+                .ok_or(FaceletError::UnknownColor(*c))?;
+            colors[sticker] = CENTER_COLORS[FACELET_FACES[i] as usize];
+        }
+
+        // start with every piece missing, so a piece that shows up twice can be found
+        let missing = Piece { pos: -1, ori: 0 };
+        let mut cube = Cube {
+            corners: [missing; 8],
+            edges: [missing; 12],
+        };
+
+        for (pos, position_stickers) in CORNER_TABLE.iter().enumerate() {
+            let stickers = position_stickers.map(|sticker| colors[facelet_index(&sticker)]);
+            let kind = PieceKind::Corners;
+            let (piece, ori) = find_piece(&CORNER_COLORS, &stickers)
+                .ok_or(FaceletError::InvalidPiece { kind, pos })?;
+            if cube.corners[piece] != missing {
+                return Err(FaceletError::DuplicatePiece { kind, piece });
+            }
+            cube.corners[piece] = Piece {
+                pos: pos as i32,
+                ori,
+            };
+        }
+
+        for (pos, position_stickers) in EDGE_TABLE.iter().enumerate() {
+            let stickers = position_stickers.map(|sticker| colors[facelet_index(&sticker)]);
+            let kind = PieceKind::Edges;
+            let (piece, ori) = find_piece(&EDGE_COLORS, &stickers)
+                .ok_or(FaceletError::InvalidPiece { kind, pos })?;
+            if cube.edges[piece] != missing {
+                return Err(FaceletError::DuplicatePiece { kind, piece });
+            }
+            cube.edges[piece] = Piece {
+                pos: pos as i32,
+                ori,
+            };
+        }
+
+        // every piece is there once, but it still might not be solvable
+        cube.validate().map_err(FaceletError::Unsolvable)?;
+
+        Ok(cube)
+    }
+
+    // Check that the cube can be reached from solved by turning the faces
+    //  Cubes from make_move always can, but anything from outside should be checked,
+    //  since the solver and the PDBs assume every cube is valid.
+    pub fn validate(&self) -> Result<(), CubeError> {
+        let corners_odd = check_pieces(&self.corners, PieceKind::Corners)?;
+        let edges_odd = check_pieces(&self.edges, PieceKind::Edges)?;
+
+        // every move twists the corners it turns by a multiple of 3 in total,
+        //  and flips an even number of edges
+        let twist = self.corners.iter().map(|piece| piece.ori).sum::<i32>() % 3;
+        if twist != 0 {
+            return Err(CubeError::CornerTwist(twist));
+        }
+
+        if self.edges.iter().map(|piece| piece.ori).sum::<i32>() % 2 != 0 {
+            return Err(CubeError::EdgeFlip);
+        }
+
+        // every quarter turn is a 4 cycle of corners and of edges, so both change parity
+        if corners_odd != edges_odd {
+            return Err(CubeError::ParityMismatch);
+        }
+
+        Ok(())
+    }
+
+    // Conjugate the cube by a symmetry (see Symmetry)
+    //  the new cube is exactly as far from solved as this one
+    pub fn conjugate(&self, sym: &Symmetry) -> Cube {
+        let mut cube = *self;
+
+        for (i, piece) in self.corners.iter().enumerate() {
+            let (new_piece, state) = sym.conjugate_corner(i, *piece);
+            cube.corners[new_piece] = state;
+        }
+
+        for (i, piece) in self.edges.iter().enumerate() {
+            let (new_piece, state) = sym.conjugate_edge(i, *piece);
+            cube.edges[new_piece] = state;
+        }
+
+        cube
+    }
+}
+
+// Everything that can make a cube impossible to reach by turning the faces
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CubeError {
+    // a piece's position or orientation is out of range
+    InvalidPiece {
+        kind: PieceKind,
+        piece: usize,
+        state: Piece,
+    },
+    // more than one piece is in the same position
+    DuplicatePosition {
+        kind: PieceKind,
+        pos: i32,
+    },
+    // the corner orientations don't add up to a multiple of 3 (a corner has been twisted)
+    //  holds what they add up to, mod 3
+    CornerTwist(i32),
+    // the edge orientations add up to an odd number (an edge has been flipped)
+    EdgeFlip,
+    // one of the corners and edges is an odd permutation and the other is even
+    //  (two pieces have been swapped)
+    ParityMismatch,
+}
+
+// what one piece of a kind is called in errors
+fn piece_name(kind: PieceKind) -> &'static str {
+    match kind {
+        PieceKind::Corners => "corner",
+        PieceKind::Edges => "edge",
+    }
+}
+
+impl fmt::Display for CubeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CubeError::InvalidPiece { kind, piece, state } => write!(
+                f,
+                "{} {} has an invalid position ({}) or orientation ({})",
+                piece_name(*kind),
+                piece,
+                state.pos,
+                state.ori
+            ),
+            CubeError::DuplicatePosition { kind, pos } => {
+                write!(
+                    f,
+                    "more than one {} is in position {}",
+                    piece_name(*kind),
+                    pos
+                )
+            }
+            CubeError::CornerTwist(twist) => write!(
+                f,
+                "a corner is twisted (the corner orientations add up to {} mod 3)",
+                twist
+            ),
+            CubeError::EdgeFlip => write!(
+                f,
+                "an edge is flipped (the edge orientations add up to an odd number)"
+            ),
+            CubeError::ParityMismatch => write!(
+                f,
+                "two pieces are swapped (the corner and edge permutations have different parity)"
+            ),
+        }
+    }
+}
+
+// Check that every piece of one kind has a valid position and orientation,
+//  and that no two are in the same position
+//  returns true if the pieces are an odd permutation
+fn check_pieces(pieces: &[Piece], kind: PieceKind) -> Result<bool, CubeError> {
+    let num_slots = kind.num_slots();
+    let orientation_base = kind.orientation_base() as i32;
+
+    let mut taken = [false; 12];
+    for (i, piece) in pieces.iter().enumerate() {
+        if !(0..num_slots as i32).contains(&piece.pos)
+            || !(0..orientation_base).contains(&piece.ori)
+        {
+            return Err(CubeError::InvalidPiece {
+                kind,
+                piece: i,
+                state: *piece,
+            });
+        }
+
+        if taken[piece.pos as usize] {
+            return Err(CubeError::DuplicatePosition {
+                kind,
+                pos: piece.pos,
+            });
+        }
+        taken[piece.pos as usize] = true;
+    }
+
+    // a cycle of k pieces takes k - 1 swaps
+    let mut swaps = 0;
+    let mut visited = [false; 12];
+    for start in 0..num_slots {
+        if visited[start] {
+            continue;
+        }
+
+        let mut i = start;
+        while !visited[i] {
+            visited[i] = true;
+            i = pieces[i].pos as usize;
+            swaps += 1;
+        }
+        swaps -= 1;
+    }
+
+    Ok(swaps % 2 == 1)
+}
+
+// The state each piece ends up in after each move, for every state it can start in
+//  corners[move][pos * 3 + ori] and edges[move][pos * 2 + ori], with the moves numbered by move_index
+//  This way make_move doesn't have to find which pieces are in the cycles of the move,
+//  it just looks up every piece.
+struct MoveTables {
+    corners: [[Piece; 24]; 18],
+    edges: [[Piece; 24]; 18],
+}
+
+static MOVE_TABLES: LazyLock<MoveTables> = LazyLock::new(build_move_tables);
+
+// The index of a move in the move tables, in the same order as the Face enum
+fn move_index(mv: Move) -> usize {
+    let turn = match mv.coeff {
+        -1 => 0,
+        1 => 1,
+        2 | -2 => 2,
+        _ => panic!("Invalid rotation coefficent: {}", mv.coeff),
+    };
+
+    mv.face as usize * 3 + turn
+}
+
+// Fill in the move tables by tracking a piece through each move
+fn build_move_tables() -> MoveTables {
+    let mut tables = MoveTables {
+        corners: [[Piece { pos: 0, ori: 0 }; 24]; 18],
+        edges: [[Piece { pos: 0, ori: 0 }; 24]; 18],
+    };
+
+    for mv in ALL_MOVES {
+        let m = move_index(mv);
+
+        for pos in 0..8 {
+            for ori in 0..3 {
+                let mut cube = Cube::new();
+                cube.corners[pos].ori = ori as i32;
+                cycle_move(&mut cube, mv);
+                tables.corners[m][pos * 3 + ori] = cube.corners[pos];
+            }
+        }
+
+        for pos in 0..12 {
+            for ori in 0..2 {
+                let mut cube = Cube::new();
+                cube.edges[pos].ori = ori as i32;
+                cycle_move(&mut cube, mv);
+                tables.edges[m][pos * 2 + ori] = cube.edges[pos];
+            }
+        }
+    }
+
+    tables
+}
+
+// Make a move by cycling the pieces in the move's cycles
+//  too slow for the solver, this is just used to build the move tables
+fn cycle_move(cube: &mut Cube, mv: Move) {
+    // Get the cycles from the move tables
+    // if coeff is negative, flip the cycle around
+    let corner_pos_cycle: [u8; 4] = {
+        if mv.coeff == -1 {
+            let mut tmp = CORNER_MOVE_TABLE[mv.face as usize];
+            tmp.reverse();
+            tmp
+        } else {
+            CORNER_MOVE_TABLE[mv.face as usize]
+        }
+    };
+
+    let edge_pos_cycle: [u8; 4] = {
+        if mv.coeff == -1 {
+            let mut tmp = EDGE_MOVE_TABLE[mv.face as usize];
+            tmp.reverse();
+            tmp
+        } else {
+            EDGE_MOVE_TABLE[mv.face as usize]
+        }
+    };
+
+    // if double rotation, there are no orientation changes
+    //  so we can just end after these 4 cycles
+    if mv.coeff.abs() == 2 {
+        cycle_pieces(&mut cube.corners, &corner_pos_cycle);
+        cycle_pieces(&mut cube.corners, &corner_pos_cycle);
+        cycle_pieces(&mut cube.edges, &edge_pos_cycle);
+        cycle_pieces(&mut cube.edges, &edge_pos_cycle);
+        return;
+    }
+
+    // Corners
+    {
+        // cycle pieces
+        let corner_cycle = cycle_pieces(&mut cube.corners, &corner_pos_cycle);
+
+        // orient pieces
+        for (i, &piece) in corner_cycle.iter().enumerate() {
+            // Different moves have differnt rotation profiles
+            let rotation = match mv.face {
+                Face::F | Face::L => get_rotation(mv.coeff, i),
+                Face::B | Face::R => get_rotation(-mv.coeff, i),
+                Face::U | Face::D => 0,
+            };
+
+            // add rotation
+            cube.corners[piece].ori = (cube.corners[piece].ori + rotation) % 3;
+        }
+    }
+
+    // Edges
+    {
+        // cycle pieces
+        let edge_cycle = cycle_pieces(&mut cube.edges, &edge_pos_cycle);
+
+        // orient pieces (Only changes on F or B moves)
+
+        if mv.face == Face::F || mv.face == Face::B {
+            for &piece in &edge_cycle {
+                cube.edges[piece].ori = (cube.edges[piece].ori + 1) % 2;
+            }
+        }
+    }
 }
 
 fn cycle_pieces<const N: usize>(pieces: &mut [Piece; N], pos_cycle: &[u8; 4]) -> [usize; 4] {
@@ -203,7 +605,7 @@ pub fn is_solved(&self) -> bool {
     }
 
     // return for orientation changes
-    return piece_cycle;
+    piece_cycle
 }
 
 // Calculate how much to rotate by
@@ -217,6 +619,165 @@ fn get_rotation(coeff: i8, i: usize) -> i32 {
     }
 }
 
+// vvv Symmetries vvv
+//
+// The cube has 48 symmetries: the 24 ways to rotate it, and each of those mirrored.
+//  Conjugating a cube by one (turn the whole cube, then repaint the pieces so the centers
+//  are back where they started) gives a state with the same distance from solved,
+//  since every move turns into another move.
+//
+// The symmetries are worked out from where each sticker is in 3D (x = R, y = U, z = F),
+//  using the same sticker tables that are used for printing.
+
+pub const NUM_SYMMETRIES: usize = 48;
+
+// The conjugation tables for one symmetry
+//  corner_pos[slot]: the slot that it is sent to
+//  corner_stickers[slot][i]: which sticker of the new slot sticker i is sent to
+//      (stickers are numbered in the same order as CORNER_TABLE / EDGE_TABLE)
+#[derive(Clone, Copy, Debug)]
+pub struct Symmetry {
+    pub corner_pos: [u8; 8],
+    pub corner_stickers: [[u8; 3]; 8],
+    pub edge_pos: [u8; 12],
+    pub edge_stickers: [[u8; 2]; 12],
+    // true for the 24 symmetries that mirror the cube
+    pub mirrored: bool,
+}
+
+impl Symmetry {
+    // Where a corner ends up when the cube is conjugated
+    //  piece: which corner it is, state: where it is
+    //  returns the corner it is repainted as and where that corner is
+    pub fn conjugate_corner(&self, piece: usize, state: Piece) -> (usize, Piece) {
+        conjugate_piece(&self.corner_pos, &self.corner_stickers, piece, state)
+    }
+
+    // Same as conjugate_corner, for edges
+    pub fn conjugate_edge(&self, piece: usize, state: Piece) -> (usize, Piece) {
+        conjugate_piece(&self.edge_pos, &self.edge_stickers, piece, state)
+    }
+}
+
+fn conjugate_piece<const S: usize>(
+    slots: &[u8],
+    stickers: &[[u8; S]],
+    piece: usize,
+    state: Piece,
+) -> (usize, Piece) {
+    let base = S as i32;
+
+    // the piece's first color is on this sticker of its slot
+    let sticker = ((base - state.ori) % base) as usize;
+
+    // the first color gets repainted as whichever color is on the sticker its home one goes to
+    let color = stickers[piece][0] as i32;
+    let new_sticker = stickers[state.pos as usize][sticker] as i32;
+
+    (
+        slots[piece] as usize,
+        Piece {
+            pos: slots[state.pos as usize] as i32,
+            ori: (color - new_sticker + base) % base,
+        },
+    )
+}
+
+// All 48 symmetries, the identity first
+pub fn symmetries() -> Vec<Symmetry> {
+    // every symmetry is an axis permutation with some of the axes flipped
+    const AXIS_PERMS: [[usize; 3]; 6] = [
+        [0, 1, 2],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [2, 1, 0],
+    ];
+    const ODD_PERMS: [bool; 6] = [false, true, true, false, false, true];
+
+    let mut syms = Vec::with_capacity(NUM_SYMMETRIES);
+
+    for (perm, odd) in AXIS_PERMS.iter().zip(ODD_PERMS) {
+        for flips in 0..8u32 {
+            let transform = |v: Vec3| -> Vec3 {
+                array::from_fn(|i| {
+                    if flips & (1 << i) == 0 {
+                        v[perm[i]]
+                    } else {
+                        -v[perm[i]]
+                    }
+                })
+            };
+
+            let (corner_pos, corner_stickers) = map_slots(&CORNER_TABLE, transform);
+            let (edge_pos, edge_stickers) = map_slots(&EDGE_TABLE, transform);
+
+            syms.push(Symmetry {
+                corner_pos,
+                corner_stickers,
+                edge_pos,
+                edge_stickers,
+                mirrored: odd != (flips.count_ones() % 2 == 1),
+            });
+        }
+    }
+
+    syms
+}
+
+type Vec3 = [i32; 3];
+
+// The direction a face points in, and the directions its rows and columns go in
+fn face_axes(face: Face) -> [Vec3; 3] {
+    match face {
+        Face::U => [[0, 1, 0], [0, 0, 1], [1, 0, 0]],
+        Face::D => [[0, -1, 0], [0, 0, -1], [1, 0, 0]],
+        Face::F => [[0, 0, 1], [0, -1, 0], [1, 0, 0]],
+        Face::B => [[0, 0, -1], [0, -1, 0], [-1, 0, 0]],
+        Face::R => [[1, 0, 0], [0, -1, 0], [0, 0, -1]],
+        Face::L => [[-1, 0, 0], [0, -1, 0], [0, 0, 1]],
+    }
+}
+
+// Where a sticker is: the cubie it is on and the direction it faces
+fn sticker_location(sticker: &(Face, u8, u8)) -> (Vec3, Vec3) {
+    let [normal, row, col] = face_axes(sticker.0);
+    let (r, c) = (sticker.1 as i32 - 1, sticker.2 as i32 - 1);
+
+    (
+        array::from_fn(|i| normal[i] + row[i] * r + col[i] * c),
+        normal,
+    )
+}
+
+// Find where a transform sends every slot and sticker of a sticker table
+fn map_slots<const N: usize, const S: usize>(
+    table: &[[(Face, u8, u8); S]; N],
+    transform: impl Fn(Vec3) -> Vec3,
+) -> ([u8; N], [[u8; S]; N]) {
+    let mut slots = [0u8; N];
+    let mut stickers = [[0u8; S]; N];
+
+    for slot in 0..N {
+        let cubie = transform(sticker_location(&table[slot][0]).0);
+        let new_slot = (0..N)
+            .find(|&s| sticker_location(&table[s][0]).0 == cubie)
+            .expect("Symmetry moved a piece off the cube");
+        slots[slot] = new_slot as u8;
+
+        for i in 0..S {
+            let normal = transform(sticker_location(&table[slot][i]).1);
+            stickers[slot][i] = (0..S)
+                .find(|&j| sticker_location(&table[new_slot][j]).1 == normal)
+                .expect("Symmetry moved a sticker off its piece")
+                as u8;
+        }
+    }
+
+    (slots, stickers)
+}
+
 // vvv Printing and Diagnostics vvv
 
 // These are for printint out the cube
@@ -260,12 +821,9 @@ fn fill_state(cube: &Cube) -> CubeState {
     // NOTE: A premade state with the centers already done would be faster
     //       but this function isn't used enough for that to be worth it.
 
-    state[Face::U as usize][1][1] = 'W';
-    state[Face::R as usize][1][1] = 'R';
-    state[Face::F as usize][1][1] = 'G';
-    state[Face::D as usize][1][1] = 'Y';
-    state[Face::L as usize][1][1] = 'O';
-    state[Face::B as usize][1][1] = 'B';
+    for (face, color) in CENTER_COLORS.iter().enumerate() {
+        state[face][1][1] = *color;
+    }
 
     // Corners
 
@@ -362,3 +920,492 @@ fn print_state(state: &CubeState) {
 
     println!();
 }
+
+// vvv Facelet strings vvv
+//
+// The usual way to write down a cube's state, used by Kociemba's solver and most other tools:
+//  the 9 stickers of each face in the order U R F D L B, row by row as the faces are printed,
+//  with each sticker written as the face whose center is the same color.
+//  e.g. solved is UUUUUUUUURRRRRRRRRFFFFFFFFFDDDDDDDDDLLLLLLLLLBBBBBBBBB
+// When reading one, any 6 characters can be used for the colors, as long as the centers are
+//  all different, so the colors themselves (e.g. from a photo) work too.
+
+// The order of the faces in a facelet string
+const FACELET_FACES: [Face; 6] = [Face::U, Face::R, Face::F, Face::D, Face::L, Face::B];
+
+// The letter for each face, in the same order as the Face enum
+const FACE_LETTERS: [char; 6] = ['U', 'R', 'F', 'L', 'B', 'D'];
+
+// Everything that can be wrong with a facelet string
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FaceletError {
+    // there have to be 54 stickers (not counting whitespace)
+    WrongLength(usize),
+    // more than one center is the same color
+    DuplicateCenter(char),
+    // a sticker isn't the color of any center
+    UnknownColor(char),
+    // the stickers in a position aren't the colors of any piece
+    InvalidPiece { kind: PieceKind, pos: usize },
+    // the same piece is in more than one position
+    DuplicatePiece { kind: PieceKind, piece: usize },
+    // the pieces are all there, but the cube can't be solved
+    Unsolvable(CubeError),
+}
+
+impl fmt::Display for FaceletError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FaceletError::WrongLength(len) => {
+                write!(f, "expected 54 stickers, found {}", len)
+            }
+            FaceletError::DuplicateCenter(color) => {
+                write!(f, "more than one center is '{}'", color)
+            }
+            FaceletError::UnknownColor(color) => {
+                write!(f, "'{}' is not the color of any center", color)
+            }
+            FaceletError::InvalidPiece { kind, pos } => write!(
+                f,
+                "the stickers of the {} in position {} aren't the colors of any {}",
+                piece_name(*kind),
+                pos,
+                piece_name(*kind)
+            ),
+            FaceletError::DuplicatePiece { kind, piece } => {
+                let colors = match kind {
+                    PieceKind::Corners => CORNER_COLORS[*piece],
+                    PieceKind::Edges => EDGE_COLORS[*piece],
+                };
+                write!(
+                    f,
+                    "the {} {} is in more than one position",
+                    colors,
+                    piece_name(*kind)
+                )
+            }
+            FaceletError::Unsolvable(e) => write!(f, "the cube can't be solved: {}", e),
+        }
+    }
+}
+
+// Where a sticker from CORNER_TABLE / EDGE_TABLE is in a facelet string
+fn facelet_index(sticker: &(Face, u8, u8)) -> usize {
+    let (face, row, col) = *sticker;
+    let face = FACELET_FACES.iter().position(|f| *f == face).unwrap();
+    face * 9 + row as usize * 3 + col as usize
+}
+
+// The face (as in the Face enum) that has a color in the center
+fn color_face(color: char) -> usize {
+    CENTER_COLORS
+        .iter()
+        .position(|c| *c == color)
+        .expect("Unknown Color")
+}
+
+// Find the piece with these stickers, in the order of the stickers of its position,
+//  and its orientation (the reverse of fill_state)
+fn find_piece<const S: usize>(piece_colors: &[&str], stickers: &[char; S]) -> Option<(usize, i32)> {
+    for (piece, colors) in piece_colors.iter().enumerate() {
+        let colors: Vec<char> = colors.chars().collect();
+        for ori in 0..S {
+            if (0..S).all(|i| stickers[i] == colors[(i + ori) % S]) {
+                return Some((piece, ori as i32));
+            }
+        }
+    }
+
+    None
+}
+
+// A cube scrambled with up to 30 random moves, for the tests here and in index.rs
+#[cfg(test)]
+pub(crate) fn random_cube(rng: &mut rand::rngs::StdRng) -> Cube {
+    use rand::Rng;
+
+    let mut cube = Cube::new();
+    let len = rng.random_range(0..30);
+    for mv in crate::scramble::generate_scramble_with_rng(len, rng) {
+        cube.make_move(mv);
+    }
+    cube
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    // make_move (with the move tables) does the same as cycling the pieces
+    #[test]
+    fn move_tables_match_cycling() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for _ in 0..1000 {
+            let cube = random_cube(&mut rng);
+
+            for mv in ALL_MOVES {
+                let mut moved = cube;
+                moved.make_move(mv);
+
+                let mut expected = cube;
+                cycle_move(&mut expected, mv);
+
+                assert_eq!(moved, expected, "move {:?} on {:?}", mv, cube);
+            }
+        }
+    }
+
+    #[test]
+    fn scrambled_cubes_are_valid() {
+        let mut rng = StdRng::seed_from_u64(2);
+
+        for _ in 0..1000 {
+            let cube = random_cube(&mut rng);
+            assert_eq!(cube.validate(), Ok(()), "{:?}", cube);
+            assert_eq!(Cube::from_pieces(cube.corners, cube.edges), Ok(cube));
+        }
+    }
+
+    #[test]
+    fn twisted_corner_is_rejected() {
+        let mut cube = Cube::new();
+        cube.corners[3].ori = 1;
+        assert_eq!(cube.validate(), Err(CubeError::CornerTwist(1)));
+        cube.corners[3].ori = 2;
+        assert_eq!(cube.validate(), Err(CubeError::CornerTwist(2)));
+
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..100 {
+            let mut cube = random_cube(&mut rng);
+            let c = rng.random_range(0..8);
+            cube.corners[c].ori = (cube.corners[c].ori + 1) % 3;
+
+            let twist = cube.corners.iter().map(|piece| piece.ori).sum::<i32>() % 3;
+            assert_eq!(twist, 1);
+            assert_eq!(cube.validate(), Err(CubeError::CornerTwist(twist)));
+        }
+    }
+
+    #[test]
+    fn flipped_edge_is_rejected() {
+        let mut cube = Cube::new();
+        cube.edges[5].ori = 1;
+        assert_eq!(cube.validate(), Err(CubeError::EdgeFlip));
+
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..100 {
+            let mut cube = random_cube(&mut rng);
+            let e = rng.random_range(0..12);
+            cube.edges[e].ori ^= 1;
+            assert_eq!(cube.validate(), Err(CubeError::EdgeFlip));
+        }
+    }
+
+    #[test]
+    fn single_swap_is_rejected() {
+        let swap = |pieces: &mut [Piece], a: usize, b: usize| {
+            let pos = pieces[a].pos;
+            pieces[a].pos = pieces[b].pos;
+            pieces[b].pos = pos;
+        };
+
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..100 {
+            let cube = random_cube(&mut rng);
+            let (c, e) = (rng.random_range(0..8), rng.random_range(0..12));
+            let (other_c, other_e) = ((c + 1) % 8, (e + rng.random_range(1..12)) % 12);
+
+            let mut corners_swapped = cube;
+            swap(&mut corners_swapped.corners, c, other_c);
+            assert_eq!(corners_swapped.validate(), Err(CubeError::ParityMismatch));
+
+            let mut edges_swapped = cube;
+            swap(&mut edges_swapped.edges, e, other_e);
+            assert_eq!(edges_swapped.validate(), Err(CubeError::ParityMismatch));
+
+            // swapping both keeps the parities the same as each other
+            let mut both_swapped = corners_swapped;
+            swap(&mut both_swapped.edges, e, other_e);
+            assert_eq!(both_swapped.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn duplicate_pieces_are_rejected() {
+        let mut cube = Cube::new();
+        cube.corners[6].pos = 2;
+        assert_eq!(
+            cube.validate(),
+            Err(CubeError::DuplicatePosition {
+                kind: PieceKind::Corners,
+                pos: 2
+            })
+        );
+
+        let mut cube = Cube::new();
+        cube.edges[11].pos = 0;
+        assert_eq!(
+            Cube::from_pieces(cube.corners, cube.edges),
+            Err(CubeError::DuplicatePosition {
+                kind: PieceKind::Edges,
+                pos: 0
+            })
+        );
+    }
+
+    #[test]
+    fn out_of_range_pieces_are_rejected() {
+        let mut cube = Cube::new();
+        cube.corners[1].pos = 8;
+        assert_eq!(
+            cube.validate(),
+            Err(CubeError::InvalidPiece {
+                kind: PieceKind::Corners,
+                piece: 1,
+                state: Piece { pos: 8, ori: 0 }
+            })
+        );
+
+        let mut cube = Cube::new();
+        cube.edges[4].ori = 2;
+        assert_eq!(
+            cube.validate(),
+            Err(CubeError::InvalidPiece {
+                kind: PieceKind::Edges,
+                piece: 4,
+                state: Piece { pos: 4, ori: 2 }
+            })
+        );
+    }
+
+    #[test]
+    fn move_then_inverse_is_solved() {
+        for mv in ALL_MOVES {
+            let mut cube = Cube::new();
+            cube.make_move(mv);
+            assert!(!cube.is_solved(), "{:?}", mv);
+
+            let coeff = if mv.coeff == 2 { 2 } else { -mv.coeff };
+            cube.make_move(Move {
+                face: mv.face,
+                coeff,
+            });
+            assert!(cube.is_solved(), "{:?}", mv);
+        }
+    }
+
+    #[test]
+    fn symmetries_keep_solved_cube_solved() {
+        let syms = symmetries();
+        assert_eq!(syms.len(), NUM_SYMMETRIES);
+
+        for (s, sym) in syms.iter().enumerate() {
+            assert!(Cube::new().conjugate(sym).is_solved(), "symmetry {}", s);
+        }
+    }
+
+    // conjugating by a symmetry turns every move into another move
+    #[test]
+    fn symmetries_commute_with_moves() {
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for (s, sym) in symmetries().iter().enumerate() {
+            // the move each move turns into
+            let mut conjugate_moves = [0usize; 18];
+            for (m, mv) in ALL_MOVES.iter().enumerate() {
+                let mut cube = Cube::new();
+                cube.make_move(*mv);
+                let target = cube.conjugate(sym);
+
+                conjugate_moves[m] = (0..ALL_MOVES.len())
+                    .find(|&n| {
+                        let mut other = Cube::new();
+                        other.make_move(ALL_MOVES[n]);
+                        other == target
+                    })
+                    .unwrap_or_else(|| {
+                        panic!("symmetry {} does not turn move {} into a move", s, m)
+                    });
+            }
+
+            for _ in 0..20 {
+                let cube = random_cube(&mut rng);
+
+                for (m, mv) in ALL_MOVES.iter().enumerate() {
+                    let mut moved = cube;
+                    moved.make_move(*mv);
+
+                    let mut expected = cube.conjugate(sym);
+                    expected.make_move(ALL_MOVES[conjugate_moves[m]]);
+
+                    assert_eq!(
+                        moved.conjugate(sym),
+                        expected,
+                        "symmetry {} and move {} on {:?}",
+                        s,
+                        m,
+                        cube
+                    );
+                }
+            }
+        }
+    }
+
+    const SOLVED: &str = "UUUUUUUUURRRRRRRRRFFFFFFFFFDDDDDDDDDLLLLLLLLLBBBBBBBBB";
+
+    // a facelet string with some of its stickers changed
+    fn with_stickers(facelets: &str, changes: &[((Face, u8, u8), char)]) -> String {
+        let mut stickers: Vec<char> = facelets.chars().collect();
+        for (sticker, color) in changes {
+            stickers[facelet_index(sticker)] = *color;
+        }
+        stickers.into_iter().collect()
+    }
+
+    #[test]
+    fn solved_facelets() {
+        assert_eq!(Cube::new().to_facelets(), SOLVED);
+        assert_eq!(Cube::from_facelets(SOLVED), Ok(Cube::new()));
+    }
+
+    // the same string as other tools give for R on a solved cube
+    #[test]
+    fn facelets_after_r() {
+        const AFTER_R: &str = "UUFUUFUUFRRRRRRRRRFFDFFDFFDDDBDDBDDBLLLLLLLLLUBBUBBUBB";
+
+        let mut cube = Cube::new();
+        cube.make_move(Move {
+            face: Face::R,
+            coeff: 1,
+        });
+        assert_eq!(cube.to_facelets(), AFTER_R);
+        assert_eq!(Cube::from_facelets(AFTER_R), Ok(cube));
+    }
+
+    #[test]
+    fn facelets_round_trip() {
+        let mut rng = StdRng::seed_from_u64(6);
+
+        for _ in 0..1000 {
+            let cube = random_cube(&mut rng);
+            let facelets = cube.to_facelets();
+            assert_eq!(Cube::from_facelets(&facelets), Ok(cube), "{}", facelets);
+        }
+    }
+
+    #[test]
+    fn facelets_can_use_any_colors_and_whitespace() {
+        let mut cube = Cube::new();
+        cube.make_move(Move {
+            face: Face::F,
+            coeff: 2,
+        });
+
+        let colors: String = cube
+            .to_facelets()
+            .chars()
+            .map(|c| match c {
+                'U' => 'W',
+                'R' => 'R',
+                'F' => 'G',
+                'D' => 'Y',
+                'L' => 'O',
+                _ => 'B',
+            })
+            .collect();
+        let spaced: Vec<&str> = (0..6).map(|i| &colors[i * 9..(i + 1) * 9]).collect();
+
+        assert_eq!(Cube::from_facelets(&spaced.join("\n")), Ok(cube));
+    }
+
+    #[test]
+    fn facelets_wrong_length() {
+        assert_eq!(
+            Cube::from_facelets(&SOLVED[1..]),
+            Err(FaceletError::WrongLength(53))
+        );
+        assert_eq!(
+            Cube::from_facelets(&format!("{}U", SOLVED)),
+            Err(FaceletError::WrongLength(55))
+        );
+        assert_eq!(Cube::from_facelets(""), Err(FaceletError::WrongLength(0)));
+    }
+
+    #[test]
+    fn facelets_duplicate_center() {
+        let facelets = with_stickers(SOLVED, &[((Face::F, 1, 1), 'R')]);
+        assert_eq!(
+            Cube::from_facelets(&facelets),
+            Err(FaceletError::DuplicateCenter('R'))
+        );
+    }
+
+    #[test]
+    fn facelets_unknown_color() {
+        let facelets = with_stickers(SOLVED, &[((Face::D, 0, 0), 'X')]);
+        assert_eq!(
+            Cube::from_facelets(&facelets),
+            Err(FaceletError::UnknownColor('X'))
+        );
+    }
+
+    // too many of one color and too few of another
+    #[test]
+    fn facelets_wrong_color_counts() {
+        // the ULB corner becomes F L B, which isn't a corner
+        let facelets = with_stickers(SOLVED, &[((Face::U, 0, 0), 'F')]);
+        assert_eq!(
+            Cube::from_facelets(&facelets),
+            Err(FaceletError::InvalidPiece {
+                kind: PieceKind::Corners,
+                pos: 5
+            })
+        );
+
+        // the UB edge becomes a second DB edge
+        let facelets = with_stickers(SOLVED, &[((Face::U, 0, 1), 'D')]);
+        assert_eq!(
+            Cube::from_facelets(&facelets),
+            Err(FaceletError::DuplicatePiece {
+                kind: PieceKind::Edges,
+                piece: 10
+            })
+        );
+    }
+
+    // every piece is there once, but the cube can't be solved
+    #[test]
+    fn facelets_unsolvable() {
+        // twist the UFR corner
+        let twisted = with_stickers(
+            SOLVED,
+            &[
+                ((Face::U, 2, 2), 'R'),
+                ((Face::R, 0, 0), 'F'),
+                ((Face::F, 0, 2), 'U'),
+            ],
+        );
+        assert_eq!(
+            Cube::from_facelets(&twisted),
+            Err(FaceletError::Unsolvable(CubeError::CornerTwist(1)))
+        );
+
+        // flip the UF edge
+        let flipped = with_stickers(SOLVED, &[((Face::U, 2, 1), 'F'), ((Face::F, 0, 1), 'U')]);
+        assert_eq!(
+            Cube::from_facelets(&flipped),
+            Err(FaceletError::Unsolvable(CubeError::EdgeFlip))
+        );
+
+        // swap the UF and UR edges
+        let swapped = with_stickers(SOLVED, &[((Face::F, 0, 1), 'R'), ((Face::R, 0, 1), 'F')]);
+        assert_eq!(
+            Cube::from_facelets(&swapped),
+            Err(FaceletError::Unsolvable(CubeError::ParityMismatch))
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use crate::cube::Cube;
+use crate::pdb::PDB;
+
+// Estimates of how many moves a cube is from solved, used to prune the solver's search
+//
+// For the solver to find paths of the length it is asked for, an estimate should never be
+//  more than the real distance (an admissible heuristic).
+// The solver can search with several threads, so heuristics have to be shareable between them.
+pub trait Heuristic: Sync {
+    fn estimate(&self, cube: &Cube) -> i32;
+
+    // The estimate of every table that makes up this heuristic, in order
+    //  used to see how much each one contributes, a single table just pushes its estimate
+    fn components(&self, cube: &Cube, out: &mut Vec<i32>) {
+        out.push(self.estimate(cube));
+    }
+
+    // the number of values components pushes
+    fn num_components(&self) -> usize {
+        1
+    }
+}
+
+impl Heuristic for PDB {
+    fn estimate(&self, cube: &Cube) -> i32 {
+        self.get_heuristic(cube)
+    }
+}
+
+// so that differently typed heuristics can be combined as &dyn Heuristic
+impl<H: Heuristic + ?Sized> Heuristic for &H {
+    fn estimate(&self, cube: &Cube) -> i32 {
+        (**self).estimate(cube)
+    }
+
+    fn components(&self, cube: &Cube, out: &mut Vec<i32>) {
+        (**self).components(cube, out)
+    }
+
+    fn num_components(&self) -> usize {
+        (**self).num_components()
+    }
+}
+
+impl<H: Heuristic + ?Sized> Heuristic for Box<H> {
+    fn estimate(&self, cube: &Cube) -> i32 {
+        (**self).estimate(cube)
+    }
+
+    fn components(&self, cube: &Cube, out: &mut Vec<i32>) {
+        (**self).components(cube, out)
+    }
+
+    fn num_components(&self) -> usize {
+        (**self).num_components()
+    }
+}
+
+// The largest of some heuristics, admissible if all of them are
+//  this is how the corner and edge PDBs are normally combined
+pub struct MaxHeuristic<'a, H: Heuristic>(pub &'a [H]);
+
+impl<H: Heuristic> Heuristic for MaxHeuristic<'_, H> {
+    fn estimate(&self, cube: &Cube) -> i32 {
+        self.0.iter().map(|h| h.estimate(cube)).max().unwrap_or(0)
+    }
+
+    fn components(&self, cube: &Cube, out: &mut Vec<i32>) {
+        for h in self.0 {
+            h.components(cube, out);
+        }
+    }
+
+    fn num_components(&self) -> usize {
+        self.0.iter().map(|h| h.num_components()).sum()
+    }
+}
+
+// The sum of some heuristics
+//  only admissible if no move is counted by more than one of them (disjoint PDBs),
+//  which isn't true for the corner and edge PDBs since every move turns both,
+//  so with those it can overestimate and the solver may find longer paths than asked for
+pub struct AdditiveHeuristic<'a, H: Heuristic>(pub &'a [H]);
+
+impl<H: Heuristic> Heuristic for AdditiveHeuristic<'_, H> {
+    fn estimate(&self, cube: &Cube) -> i32 {
+        self.0.iter().map(|h| h.estimate(cube)).sum()
+    }
+
+    fn components(&self, cube: &Cube, out: &mut Vec<i32>) {
+        for h in self.0 {
+            h.components(cube, out);
+        }
+    }
+
+    fn num_components(&self) -> usize {
+        self.0.iter().map(|h| h.num_components()).sum()
+    }
+}
+
+// Always 0, which turns IDA* into plain iterative deepening
+//  only usable for short scrambles, but it can't be wrong, so it's good for testing
+pub struct ZeroHeuristic;
+
+impl Heuristic for ZeroHeuristic {
+    fn estimate(&self, _cube: &Cube) -> i32 {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Piece;
+    use crate::notation::parse_path;
+
+    // the number of pieces out of place, each counting a disjoint set of pieces
+    struct CornersOff;
+    struct EdgesOff;
+
+    fn off(pieces: &[Piece]) -> i32 {
+        pieces
+            .iter()
+            .enumerate()
+            .filter(|(i, piece)| piece.pos != *i as i32 || piece.ori != 0)
+            .count() as i32
+    }
+
+    impl Heuristic for CornersOff {
+        fn estimate(&self, cube: &Cube) -> i32 {
+            off(&cube.corners)
+        }
+    }
+
+    impl Heuristic for EdgesOff {
+        fn estimate(&self, cube: &Cube) -> i32 {
+            off(&cube.edges)
+        }
+    }
+
+    fn scrambled(scramble: &str) -> Cube {
+        let mut cube = Cube::new();
+        for mv in parse_path(scramble).unwrap() {
+            cube.make_move(mv);
+        }
+        cube
+    }
+
+    #[test]
+    fn additive_heuristic_sums_its_parts() {
+        let parts: [&dyn Heuristic; 2] = [&CornersOff, &EdgesOff];
+        let additive = AdditiveHeuristic(&parts);
+        assert_eq!(additive.num_components(), 2);
+
+        for scramble in ["R", "R U", "F2 L' D B"] {
+            let cube = scrambled(scramble);
+            let (corners, edges) = (CornersOff.estimate(&cube), EdgesOff.estimate(&cube));
+
+            assert_eq!(additive.estimate(&cube), corners + edges, "{}", scramble);
+
+            let mut components = Vec::new();
+            additive.components(&cube, &mut components);
+            assert_eq!(components, vec![corners, edges], "{}", scramble);
+        }
+
+        assert_eq!(additive.estimate(&Cube::new()), 0);
+    }
+
+    #[test]
+    fn nested_heuristics_report_every_component() {
+        let max = [CornersOff, CornersOff];
+        let parts: [&dyn Heuristic; 2] = [&MaxHeuristic(&max), &EdgesOff];
+        let additive = AdditiveHeuristic(&parts);
+        assert_eq!(additive.num_components(), 3);
+
+        let cube = scrambled("R U");
+        let mut components = Vec::new();
+        additive.components(&cube, &mut components);
+        assert_eq!(components.len(), additive.num_components());
+        assert_eq!(
+            additive.estimate(&cube),
+            CornersOff.estimate(&cube) + EdgesOff.estimate(&cube)
+        );
+    }
+}
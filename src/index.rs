@@ -0,0 +1,621 @@
+use crate::cube::{symmetries, Cube, Piece, Symmetry};
+
+// Indexing of piece subsets
+//
+// A subset is some of the pieces of one kind, e.g. all 8 corners or edges 0..6.
+//  The state of a subset is where each of its pieces is and how it is oriented,
+//  which is turned into a single number (its index) and back.
+//
+// The index is made of 2 parts:
+//  the positions: a k-of-n partial permutation (k pieces spread over n slots),
+//      ranked with a Lehmer code that only counts the slots that are still free
+//  the orientations: one digit per piece in base 3 (corners) or base 2 (edges)
+//      when the subset has every piece of its kind, the last orientation is implied
+//      (the orientations of a valid cube always add up to 0) so it isn't stored
+//
+//  index = position_code * orientation_base^(stored orientations) + orientation_code
+//
+// For all 8 corners this is the same index the original corner PDB used.
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PieceKind {
+    Corners,
+    Edges,
+}
+
+impl PieceKind {
+    // figure out which pieces a selector picks by the size of the slice it returns
+    pub fn of(selector: fn(&Cube) -> &[Piece]) -> PieceKind {
+        let cube = Cube::new();
+        if selector(&cube).len() == cube.corners.len() {
+            PieceKind::Corners
+        } else {
+            PieceKind::Edges
+        }
+    }
+
+    // the number of pieces (and positions) of this kind
+    pub fn num_slots(self) -> usize {
+        match self {
+            PieceKind::Corners => 8,
+            PieceKind::Edges => 12,
+        }
+    }
+
+    // the number of ways a piece of this kind can be oriented
+    pub fn orientation_base(self) -> usize {
+        match self {
+            PieceKind::Corners => 3,
+            PieceKind::Edges => 2,
+        }
+    }
+
+    pub fn pieces(self, cube: &Cube) -> &[Piece] {
+        match self {
+            PieceKind::Corners => &cube.corners,
+            PieceKind::Edges => &cube.edges,
+        }
+    }
+
+    pub fn pieces_mut(self, cube: &mut Cube) -> &mut [Piece] {
+        match self {
+            PieceKind::Corners => &mut cube.corners,
+            PieceKind::Edges => &mut cube.edges,
+        }
+    }
+
+    pub(crate) fn from_u8(byte: u8) -> Option<PieceKind> {
+        match byte {
+            0 => Some(PieceKind::Corners),
+            1 => Some(PieceKind::Edges),
+            _ => None,
+        }
+    }
+}
+
+// Some of the pieces of one kind, selected by their index in Cube::corners or Cube::edges
+#[derive(Clone, PartialEq, Debug)]
+pub struct PieceSubset {
+    pub kind: PieceKind,
+    pub range: std::ops::Range<usize>,
+}
+
+impl PieceSubset {
+    pub fn new(kind: PieceKind, range: std::ops::Range<usize>) -> PieceSubset {
+        assert!(
+            !range.is_empty() && range.end <= kind.num_slots(),
+            "Invalid {:?} range: {:?}",
+            kind,
+            range
+        );
+        PieceSubset { kind, range }
+    }
+
+    pub fn corners() -> PieceSubset {
+        PieceSubset::new(PieceKind::Corners, 0..8)
+    }
+
+    pub fn edges(range: std::ops::Range<usize>) -> PieceSubset {
+        PieceSubset::new(PieceKind::Edges, range)
+    }
+
+    // the number of pieces in the subset (k)
+    pub fn num_pieces(&self) -> usize {
+        self.range.end - self.range.start
+    }
+
+    pub fn orientation_base(&self) -> usize {
+        self.kind.orientation_base()
+    }
+
+    // the number of orientations that make it into the index
+    pub fn stored_orientations(&self) -> usize {
+        if self.num_pieces() == self.kind.num_slots() {
+            self.num_pieces() - 1
+        } else {
+            self.num_pieces()
+        }
+    }
+
+    // the number of different position codes, n! / (n - k)!
+    pub fn num_positions(&self) -> usize {
+        let n = self.kind.num_slots();
+        ((n - self.num_pieces() + 1)..=n).product()
+    }
+
+    // the number of different indices (every index is < size)
+    pub fn size(&self) -> usize {
+        self.num_positions()
+            * self
+                .orientation_base()
+                .pow(self.stored_orientations() as u32)
+    }
+
+    // the pieces of this subset in a cube
+    pub fn select<'a>(&self, cube: &'a Cube) -> &'a [Piece] {
+        &self.kind.pieces(cube)[self.range.clone()]
+    }
+
+    // Encode the state of the subset from a cube
+    pub fn encode_cube(&self, cube: &Cube) -> usize {
+        self.encode(self.select(cube))
+    }
+
+    // Encode the state of the subset
+    //  pieces: just the pieces in the subset (in the same order as the range)
+    pub fn encode(&self, pieces: &[Piece]) -> usize {
+        let base = self.orientation_base();
+        let n = self.kind.num_slots();
+
+        let mut orient_code: usize = 0;
+        for piece in &pieces[..self.stored_orientations()] {
+            orient_code = orient_code * base + piece.ori as usize;
+        }
+
+        // Lehmer code, but only counting the slots no earlier piece is in
+        let mut used: u32 = 0;
+        let mut perm_code: usize = 0;
+        for (i, piece) in pieces.iter().enumerate() {
+            let pos = piece.pos as u32;
+            let num_smaller = pos - (used & ((1 << pos) - 1)).count_ones();
+            perm_code = perm_code * (n - i) + num_smaller as usize;
+            used |= 1 << pos;
+        }
+
+        perm_code * base.pow(self.stored_orientations() as u32) + orient_code
+    }
+
+    // Decode an index back into the pieces of the subset
+    pub fn decode(&self, index: usize) -> Vec<Piece> {
+        let mut pieces = vec![Piece { pos: 0, ori: 0 }; self.num_pieces()];
+        self.decode_into(index, &mut pieces);
+        pieces
+    }
+
+    // Same as decode, without allocating
+    //  pieces: has to be the same length as the subset
+    pub fn decode_into(&self, index: usize, pieces: &mut [Piece]) {
+        let base = self.orientation_base();
+        let n = self.kind.num_slots();
+        let k = self.num_pieces();
+        let stored = self.stored_orientations();
+
+        // split code
+        let mut orient_code = index % base.pow(stored as u32);
+        let mut perm_code = index / base.pow(stored as u32);
+
+        // orientations, read from the last stored one to the first
+        let mut sum: usize = 0;
+        for i in (0..stored).rev() {
+            let ori = orient_code % base;
+            pieces[i].ori = ori as i32;
+            sum += ori;
+            orient_code /= base;
+        }
+
+        // the last orientation is whatever makes the sum 0
+        //  (only when the subset is the full set, otherwise every orientation is stored)
+        if stored < k {
+            pieces[k - 1].ori = ((base - (sum % base)) % base) as i32;
+        }
+
+        // Lehmer digits, read from the last piece to the first
+        let mut digits = [0usize; 12];
+        for i in (0..k).rev() {
+            digits[i] = perm_code % (n - i);
+            perm_code /= n - i;
+        }
+
+        // each digit is how many free slots are before the piece's slot
+        let mut used: u32 = 0;
+        for i in 0..k {
+            let mut pos: u32 = 0;
+            let mut free_before = 0;
+            loop {
+                if used & (1 << pos) == 0 {
+                    if free_before == digits[i] {
+                        break;
+                    }
+                    free_before += 1;
+                }
+                pos += 1;
+            }
+            pieces[i].pos = pos as i32;
+            used |= 1 << pos;
+        }
+    }
+}
+
+// vvv Symmetry reduction vvv
+//
+// States that are conjugates of each other (see cube::Symmetry) are the same distance
+//  from solved, so a table only needs one entry per class of them.
+//  Each class is represented by the smallest index in it.
+//
+// Only the symmetries that send the subset onto itself can be used
+//  (otherwise the conjugate is a state of some other pieces),
+//  which is all 48 for the corners, but only a few for most edge subsets.
+
+#[derive(Clone, Debug)]
+pub struct SymmetricSubset {
+    pub subset: PieceSubset,
+    // one for each usable symmetry, the identity first
+    tables: Vec<ConjugationTable>,
+    // first[i][pos * base + ori]: for piece i in that state, the smallest position it is
+    //  conjugated to when it becomes the first piece of the subset,
+    //  and the symmetries that do that (a bit for each table)
+    first: Vec<[(u8, u64); 24]>,
+}
+
+// A symmetry restricted to the pieces of a subset
+//  pieces[i]: which piece of the subset piece i is repainted as
+//  sources[j]: which piece of the subset is repainted as piece j (the inverse of pieces)
+//  positions[i][pos * base + ori], orientations[i][pos * base + ori]: where it ends up
+#[derive(Clone, Debug)]
+struct ConjugationTable {
+    pieces: [u8; 12],
+    sources: [u8; 12],
+    positions: [[u8; 24]; 12],
+    orientations: [[u8; 24]; 12],
+}
+
+impl SymmetricSubset {
+    pub fn new(subset: PieceSubset) -> SymmetricSubset {
+        let tables: Vec<ConjugationTable> = symmetries()
+            .iter()
+            .filter_map(|sym| conjugation_table(&subset, sym))
+            .collect();
+
+        let mut first = vec![[(u8::MAX, 0u64); 24]; subset.num_pieces()];
+        for (s, table) in tables.iter().enumerate() {
+            let i = table.sources[0] as usize;
+            for (entry, &pos) in first[i].iter_mut().zip(&table.positions[i]) {
+                if pos < entry.0 {
+                    *entry = (pos, 1 << s);
+                } else if pos == entry.0 {
+                    entry.1 |= 1 << s;
+                }
+            }
+        }
+
+        SymmetricSubset {
+            subset,
+            tables,
+            first,
+        }
+    }
+
+    // the number of symmetries that can be used (1 means there is nothing to reduce)
+    pub fn num_symmetries(&self) -> usize {
+        self.tables.len()
+    }
+
+    // The index of the representative of the class the subset's state is in
+    //  pieces: just the pieces in the subset (same as PieceSubset::encode)
+    //
+    // Indices are ordered by the positions of the pieces (first piece first), then by their
+    //  orientations, so the symmetries are narrowed down a piece at a time to the ones that
+    //  give the smallest position (then orientation), and only the one left is encoded.
+    //  The first piece is looked up in 'first' rather than trying every symmetry.
+    pub fn representative(&self, pieces: &[Piece]) -> usize {
+        let base = self.subset.orientation_base();
+        let k = pieces.len();
+
+        // where each piece is, as an index into the tables
+        let mut coords = [0usize; 12];
+        for (coord, piece) in coords.iter_mut().zip(pieces) {
+            *coord = piece.pos as usize * base + piece.ori as usize;
+        }
+
+        let mut min_pos = u8::MAX;
+        let mut candidates: u64 = 0;
+        for (first, &coord) in self.first.iter().zip(&coords) {
+            let (pos, syms) = first[coord];
+            if pos < min_pos {
+                min_pos = pos;
+                candidates = syms;
+            } else if pos == min_pos {
+                candidates |= syms;
+            }
+        }
+
+        // keep the candidates that give the smallest value for the conjugate's piece j
+        let narrow =
+            |candidates: u64, j: usize, values: fn(&ConjugationTable) -> &[[u8; 24]; 12]| {
+                let mut min = u8::MAX;
+                let mut keep: u64 = 0;
+                let mut rest = candidates;
+                while rest != 0 {
+                    let s = rest.trailing_zeros() as usize;
+                    rest &= rest - 1;
+
+                    let table = &self.tables[s];
+                    let i = table.sources[j] as usize;
+                    let value = values(table)[i][coords[i]];
+                    if value < min {
+                        min = value;
+                        keep = 1 << s;
+                    } else if value == min {
+                        keep |= 1 << s;
+                    }
+                }
+                keep
+            };
+
+        for j in 1..k {
+            if candidates.count_ones() == 1 {
+                break;
+            }
+            candidates = narrow(candidates, j, |table| &table.positions);
+        }
+        for j in 0..k {
+            if candidates.count_ones() == 1 {
+                break;
+            }
+            candidates = narrow(candidates, j, |table| &table.orientations);
+        }
+
+        // any that are left give the same state
+        let mut representative = [Piece { pos: 0, ori: 0 }; 12];
+        let table = &self.tables[candidates.trailing_zeros() as usize];
+        self.conjugate_into(table, pieces, &mut representative[..k]);
+        self.subset.encode(&representative[..k])
+    }
+
+    pub fn representative_cube(&self, cube: &Cube) -> usize {
+        self.representative(self.subset.select(cube))
+    }
+
+    // true if index (the encoding of pieces) is the representative of its class
+    //  same as representative(pieces) == index, but stops as soon as it finds a smaller one
+    pub fn is_representative(&self, index: usize, pieces: &[Piece]) -> bool {
+        let mut conjugate = [Piece { pos: 0, ori: 0 }; 12];
+
+        self.tables.iter().skip(1).all(|table| {
+            self.conjugate_into(table, pieces, &mut conjugate);
+            self.subset.encode(&conjugate[..pieces.len()]) >= index
+        })
+    }
+
+    fn conjugate_into(&self, table: &ConjugationTable, pieces: &[Piece], out: &mut [Piece]) {
+        let base = self.subset.orientation_base();
+
+        for (i, piece) in pieces.iter().enumerate() {
+            let coord = piece.pos as usize * base + piece.ori as usize;
+            out[table.pieces[i] as usize] = Piece {
+                pos: table.positions[i][coord] as i32,
+                ori: table.orientations[i][coord] as i32,
+            };
+        }
+    }
+}
+
+// The table for a symmetry, if it sends the subset onto itself
+fn conjugation_table(subset: &PieceSubset, sym: &Symmetry) -> Option<ConjugationTable> {
+    let base = subset.orientation_base();
+    let conjugate = match subset.kind {
+        PieceKind::Corners => Symmetry::conjugate_corner,
+        PieceKind::Edges => Symmetry::conjugate_edge,
+    };
+
+    let mut table = ConjugationTable {
+        pieces: [0; 12],
+        sources: [0; 12],
+        positions: [[0; 24]; 12],
+        orientations: [[0; 24]; 12],
+    };
+
+    for (i, piece) in subset.range.clone().enumerate() {
+        let (new_piece, _) = conjugate(sym, piece, Piece { pos: 0, ori: 0 });
+        if !subset.range.contains(&new_piece) {
+            return None;
+        }
+        table.pieces[i] = (new_piece - subset.range.start) as u8;
+        table.sources[new_piece - subset.range.start] = i as u8;
+
+        for pos in 0..subset.kind.num_slots() {
+            for ori in 0..base {
+                let state = Piece {
+                    pos: pos as i32,
+                    ori: ori as i32,
+                };
+                let (_, new_state) = conjugate(sym, piece, state);
+                table.positions[i][pos * base + ori] = new_state.pos as u8;
+                table.orientations[i][pos * base + ori] = new_state.ori as u8;
+            }
+        }
+    }
+
+    Some(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::cube::random_cube;
+
+    fn subsets() -> Vec<PieceSubset> {
+        vec![
+            PieceSubset::corners(),
+            PieceSubset::new(PieceKind::Corners, 0..3),
+            PieceSubset::edges(0..6),
+            PieceSubset::edges(6..12),
+            PieceSubset::edges(0..7),
+            PieceSubset::edges(4..12),
+            PieceSubset::edges(0..12),
+        ]
+    }
+
+    fn same_pieces(a: &[Piece], b: &[Piece]) -> bool {
+        a.len() == b.len()
+            && a.iter()
+                .zip(b.iter())
+                .all(|(x, y)| x.pos == y.pos && x.ori == y.ori)
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(PieceSubset::corners().size(), 88_179_840);
+        assert_eq!(PieceSubset::edges(0..6).size(), 42_577_920);
+        assert_eq!(PieceSubset::edges(0..7).size(), 510_935_040);
+        assert_eq!(PieceSubset::edges(0..8).size(), 5_109_350_400);
+    }
+
+    // every scrambled state survives encode -> decode
+    #[test]
+    fn cube_round_trip() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for subset in subsets() {
+            for _ in 0..2000 {
+                let cube = random_cube(&mut rng);
+                let pieces = subset.select(&cube);
+                let index = subset.encode(pieces);
+
+                assert!(
+                    index < subset.size(),
+                    "{:?}: {} is out of range",
+                    subset,
+                    index
+                );
+
+                let decoded = subset.decode(index);
+                assert!(
+                    same_pieces(pieces, &decoded),
+                    "{:?}: {:?} was encoded as {} but decoded as {:?}",
+                    subset,
+                    pieces,
+                    index,
+                    decoded
+                );
+            }
+        }
+    }
+
+    // indices survive decode -> encode, all of them for small subsets
+    #[test]
+    fn index_round_trip() {
+        let mut rng = StdRng::seed_from_u64(2);
+
+        for subset in subsets() {
+            let mut pieces = vec![Piece { pos: 0, ori: 0 }; subset.num_pieces()];
+            let exhaustive = subset.size() <= 20_000;
+
+            for i in 0..subset.size().min(20_000) {
+                let index = if exhaustive {
+                    i
+                } else {
+                    rng.random_range(0..subset.size())
+                };
+
+                subset.decode_into(index, &mut pieces);
+                assert_eq!(
+                    subset.encode(&pieces),
+                    index,
+                    "{:?}: decoded as {:?}",
+                    subset,
+                    pieces
+                );
+            }
+        }
+    }
+
+    // the representative is the smallest index of any conjugate
+    #[test]
+    fn representative_is_smallest_conjugate() {
+        let mut rng = StdRng::seed_from_u64(4);
+
+        for subset in subsets() {
+            let symmetric = SymmetricSubset::new(subset.clone());
+            let mut conjugate = [Piece { pos: 0, ori: 0 }; 12];
+            let k = subset.num_pieces();
+
+            for _ in 0..500 {
+                let cube = random_cube(&mut rng);
+                let pieces = subset.select(&cube);
+
+                let smallest = symmetric
+                    .tables
+                    .iter()
+                    .map(|table| {
+                        symmetric.conjugate_into(table, pieces, &mut conjugate[..k]);
+                        subset.encode(&conjugate[..k])
+                    })
+                    .min()
+                    .unwrap();
+
+                assert_eq!(symmetric.representative(pieces), smallest, "{:?}", subset);
+            }
+        }
+    }
+
+    #[test]
+    fn corners_have_every_symmetry() {
+        assert_eq!(
+            SymmetricSubset::new(PieceSubset::corners()).num_symmetries(),
+            48
+        );
+    }
+
+    // every conjugate of a state has the same representative,
+    //  and the conjugation tables agree with conjugating the whole cube
+    #[test]
+    fn symmetric_subset() {
+        let mut rng = StdRng::seed_from_u64(3);
+
+        for subset in subsets() {
+            let symmetric = SymmetricSubset::new(subset.clone());
+
+            // the symmetries the tables were made from, in the same order
+            let syms: Vec<Symmetry> = symmetries()
+                .into_iter()
+                .filter(|sym| conjugation_table(&subset, sym).is_some())
+                .collect();
+
+            for _ in 0..200 {
+                let cube = random_cube(&mut rng);
+                let index = subset.encode_cube(&cube);
+                let representative = symmetric.representative_cube(&cube);
+
+                assert!(representative <= index, "{:?}: index {}", subset, index);
+                assert_eq!(
+                    symmetric.is_representative(index, subset.select(&cube)),
+                    representative == index,
+                    "{:?}: index {} has representative {}",
+                    subset,
+                    index,
+                    representative
+                );
+
+                for (s, (sym, table)) in syms.iter().zip(symmetric.tables.iter()).enumerate() {
+                    let conjugate = cube.conjugate(sym);
+
+                    let mut pieces = [Piece { pos: 0, ori: 0 }; 12];
+                    let k = subset.num_pieces();
+                    symmetric.conjugate_into(table, subset.select(&cube), &mut pieces[..k]);
+
+                    assert!(
+                        same_pieces(&pieces[..k], subset.select(&conjugate)),
+                        "{:?}: symmetry {} table gives {:?}, conjugating the cube gives {:?}",
+                        subset,
+                        s,
+                        &pieces[..k],
+                        subset.select(&conjugate)
+                    );
+                    assert_eq!(
+                        symmetric.representative_cube(&conjugate),
+                        representative,
+                        "{:?}: symmetry {} changes the representative of index {}",
+                        subset,
+                        s,
+                        index
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,8 @@
 pub mod pdb;
+pub mod pdb_set;
 pub mod cube;
 pub mod solver;
 pub mod scramble;
-
+pub mod notation;
+pub mod index;
+pub mod heuristic;
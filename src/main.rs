@@ -1,88 +1,415 @@
+mod cli;
+
 use std::io::{self, Write};
+use std::path::Path;
 use HalfScramble::cube::{Cube, Move};
-use HalfScramble::pdb::PDB;
-use HalfScramble::scramble::{generate_scramble, invert_move, invert_path, print_path};
-use HalfScramble::solver::solve;
+use HalfScramble::heuristic::Heuristic;
+use HalfScramble::notation::{format_path, parse_path};
+use HalfScramble::pdb::EdgeTables;
+use HalfScramble::pdb_set::{resolve_pdb_dir, PDBSet, PDB_DIR_ENV};
+use HalfScramble::scramble::{generate_scramble_with_rng, invert_path, print_path};
+use HalfScramble::solver::{
+    find_solutions, solve, solve_with_stats, Budget, Constraints, NodeCounter, SolveResult,
+    SolveStats,
+};
+
+use cli::{Format, Options};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use std::env;
+use std::process;
+use std::time::Instant;
+
+// How many alternate scrambles --random-alternate picks from at most
+//  there can be a huge number of them for long scrambles
+const RANDOM_ALTERNATE_LIMIT: usize = 1000;
 
 fn main() {
-    // get length of scrable
+    // parse the command line (skip the program name)
     let args: Vec<String> = env::args().collect();
-    //let scramble_len: i32 = args[1].trim().parse().expect("Not a number");
+    let options: Options = match cli::parse_args(&args[1..]) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
 
     // load PDBS into array
-    let corner_pdb_path = "data/corner_pdb.bin";
-    let edge1_pdb_path = "data/edge_pdb_1.bin";
-    let edge2_pdb_path = "data/edge_pdb_2.bin";
-
-    let pdb_array: [PDB; 3] = [
-        PDB::new(corner_pdb_path, 0..8, |c| &c.corners, 3).expect("Error: Could not load corner PDB, please \
-        verify the pdb is at 'data/corner_pdb.bin' or rebuild it with `cargo run --release --bin build_pdb 1`"),
-        PDB::new(edge1_pdb_path, 0..8, |c| &c.edges, 2).expect("Error: Could not load edge PDB #1, please \
-        verify the pdb is at 'data/edge_pdb_1.bin' or rebuild it with `cargo run --release --bin build_pdb 2`"),
-        PDB::new(edge2_pdb_path, 4..12, |c| &c.edges, 2).expect("Error: Could not load edge PDB #2, please \
-        verify the pdb is at 'data/edge_pdb_2.bin' or rebuild it with `cargo run --release --bin build_pdb 3`"),
-    ];
-
-    println!("Loaded corner PDB from {}", corner_pdb_path);
-    println!("Loaded edge PDB #1 from {}", edge1_pdb_path);
-    println!("Loaded edge PDB #2 from {}", edge2_pdb_path);
+    let pdb_dir = resolve_pdb_dir(options.pdb_dir.as_deref());
+    let pdb_set = load_pdbs(&pdb_dir, options.edges, options.mmap, !options.batch);
+    let heuristic = pdb_set.heuristic();
 
-    let mut input = String::new();
+    if options.stats {
+        let names: Vec<&str> = pdb_set.specs().iter().map(|spec| spec.name).collect();
+        eprintln!("Heuristic wins are for: {}", names.join(" / "));
+    }
+
+    // pick a seed if one wasn't given so that it can still be shared
+    let seed: u64 = options.seed.unwrap_or_else(|| rand::rng().random());
+    let mut rngs = Rngs::new(seed);
+
+    if !options.batch && options.scramble.is_none() {
+        println!("Seed: {}", seed);
+    }
+
+    if options.batch {
+        run_batch(&options, &heuristic, &mut rngs);
+    } else if options.length.is_some() || options.scramble.is_some() {
+        for i in 0..options.count {
+            if i > 0 {
+                println!("\n--------------------\n");
+            }
+            let scramble = next_scramble(&options, &mut rngs.scramble);
+            play_round(&scramble, &heuristic, &options, &mut rngs.alternate);
+        }
+    } else {
+        run_interactive(&heuristic, &options, &mut rngs);
+    }
+}
+
+// The random number generators, both seeded from --seed
+//  the alternate scramble is picked (for --random-alternate) with its own,
+//  so picking one doesn't change the scrambles that come after it
+struct Rngs {
+    scramble: StdRng,
+    alternate: StdRng,
+}
+
+impl Rngs {
+    fn new(seed: u64) -> Rngs {
+        Rngs {
+            scramble: StdRng::seed_from_u64(seed),
+            // any constant will do, as long as the 2 seeds differ
+            alternate: StdRng::seed_from_u64(seed ^ 0x9e37_79b9_7f4a_7c15),
+        }
+    }
+}
+
+// Load the PDBs from dir, exiting with a list of the missing ones if it fails
+fn load_pdbs(dir: &Path, edges: EdgeTables, mmap: bool, verbose: bool) -> PDBSet {
+    let pdb_set = PDBSet::load_with(dir, edges, mmap).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        eprintln!(
+            "The PDB directory can be set with --pdb-dir or the {} environment variable",
+            PDB_DIR_ENV
+        );
+        process::exit(1);
+    });
+
+    if verbose {
+        for (i, spec) in pdb_set.specs().iter().enumerate() {
+            let reduced = if pdb_set.pdbs[i].is_reduced() {
+                " (symmetry reduced)"
+            } else {
+                ""
+            };
+            println!(
+                "Loaded {}{} from {}",
+                spec.name,
+                reduced,
+                pdb_set.path(i).display()
+            );
+        }
+    }
+
+    pdb_set
+}
+
+// The scramble to use for the next round, as given on the command line
+fn next_scramble(options: &Options, rng: &mut StdRng) -> Vec<Move> {
+    match (&options.scramble, options.length) {
+        (Some(scramble), _) => scramble.clone(),
+        (None, Some(scramble_len)) => generate_scramble_with_rng(scramble_len, rng),
+        (None, None) => unreachable!("no scramble or length was given"),
+    }
+}
+
+// Print every scramble and solution without waiting on the user
+fn run_batch(options: &Options, heuristic: &impl Heuristic, rngs: &mut Rngs) {
+    for _ in 0..options.count {
+        let scramble = next_scramble(options, &mut rngs.scramble);
+
+        let long_scramble = half_scramble(&scramble, heuristic, options, &mut rngs.alternate);
+        let solution = invert_path(&scramble);
 
-    print!("Enter scramble length: ");
+        match (options.format, long_scramble) {
+            (Format::Text, SolveResult::Found(long_scramble)) => {
+                println!("Scramble: {}", format_path(&long_scramble));
+                println!("Solution: {}", format_path(&solution));
+            }
+            (Format::Text, SolveResult::BudgetExceeded(reason)) => {
+                println!(
+                    "Scramble: (gave up looking for an alternate scramble: {})",
+                    reason
+                );
+                println!("Solution: {}", format_path(&solution));
+            }
+            (Format::Text, SolveResult::NoSolution) => {
+                println!("Scramble: (no alternate scramble of the requested length)");
+                println!("Solution: {}", format_path(&solution));
+            }
+            (Format::Json, long_scramble) => {
+                // gave_up is why the search stopped early, if it did
+                let (long_scramble, gave_up) = match long_scramble {
+                    SolveResult::Found(path) => {
+                        (format!("\"{}\"", format_path(&path)), String::from("null"))
+                    }
+                    SolveResult::BudgetExceeded(reason) => {
+                        (String::from("null"), format!("\"{}\"", reason))
+                    }
+                    SolveResult::NoSolution => (String::from("null"), String::from("null")),
+                };
+                println!(
+                    "{{\"length\": {}, \"scramble\": {}, \"solution\": \"{}\", \"gave_up\": {}}}",
+                    scramble.len(),
+                    long_scramble,
+                    format_path(&solution),
+                    gave_up
+                );
+            }
+        }
+    }
+}
+
+// Keep asking for scrambles until an empty line is entered or stdin is closed
+fn run_interactive(heuristic: &impl Heuristic, options: &Options, rngs: &mut Rngs) {
+    let mut prompt = "Enter scramble length or scramble: ";
+
+    while let Some(input) = read_input(prompt) {
+        // the input can either be a length for a random scramble
+        //  or a scramble that was done on a real cube
+        let scramble: Vec<Move> = match input.parse::<i32>() {
+            Ok(scramble_len) if scramble_len < 1 => {
+                println!("Error: scramble length must be at least 1");
+                Vec::new()
+            }
+            Ok(scramble_len) if scramble_len > cli::MAX_SCRAMBLE_LENGTH => {
+                println!("Error: {}", cli::scramble_too_long());
+                Vec::new()
+            }
+            Ok(scramble_len) => generate_scramble_with_rng(scramble_len, &mut rngs.scramble),
+            Err(_) => match parse_path(&input) {
+                Ok(path) if path.len() > cli::MAX_SCRAMBLE_LENGTH as usize => {
+                    println!("Error: {}", cli::scramble_too_long());
+                    Vec::new()
+                }
+                Ok(path) => path,
+                Err(e) => {
+                    println!("Error: Could not read scramble: {}", e);
+                    Vec::new()
+                }
+            },
+        };
+
+        if !scramble.is_empty() {
+            play_round(&scramble, heuristic, options, &mut rngs.alternate);
+        }
+
+        prompt =
+            "\n--------------------\nEnter scramble length or scramble (leave empty to quit): ";
+    }
+}
+
+// Returns the trimmed line, or None at the end of the input or on an empty line
+fn read_input(prompt: &str) -> Option<String> {
+    print!("{}", prompt);
     io::stdout().flush().unwrap();
 
-    io::stdin()
+    let mut input = String::new();
+    let read = io::stdin()
         .read_line(&mut input)
         .expect("Failed to read line");
     println!();
 
-    while input != "\n" {
-        let scramble_len: i32 = input.trim().parse::<i32>().expect("Not a number");
+    let input = input.trim();
+    if read == 0 || input.is_empty() {
+        None
+    } else {
+        Some(input.to_string())
+    }
+}
 
-        // create new cube and scramble
-        let mut cube = Cube::new();
-        let scramble = generate_scramble(scramble_len);
+// Find the alternate path for a scramble
+//  returns the scramble to show the user, which is as long as the original
+//  (or whatever --solution-length asks for) but does not give away the solution
+//  returns NoSolution if there is no alternate scramble of that length
+//  (or the scramble cancels itself out), or BudgetExceeded if --timeout or --max-nodes ran out before one was found
+//  with --random-alternate, it is picked at random (with alternate_rng)
+//  from the alternates of that length, and --timeout and --max-nodes cover both searches
+fn half_scramble(
+    scramble: &[Move],
+    heuristic: &impl Heuristic,
+    options: &Options,
+    alternate_rng: &mut StdRng,
+) -> SolveResult {
+    let scramble_len = scramble.len() as i32;
 
-        // This is to prevent the solution from being the inverse of the scramble
-        //      has to be computed before the cube is actualy scrambled because of consumption
-        let last_move_inv: Move = invert_move(scramble[(scramble_len - 1) as usize]);
+    // create new cube
+    let mut cube = Cube::new();
 
-        // scramble the cube
-        for mv in scramble.iter() {
-            cube.make_move(*mv);
-        }
+    // This is to prevent the solution from being the inverse of the scramble
+    //  by default only the first move (in any order with a move on the opposite face) has to be different
+    let constraints = Constraints {
+        no_shared_states: options.no_shared_states,
+        min_edit_distance: options.min_edit_distance,
+        no_shared_suffix: options.no_shared_suffix,
+        ..Constraints::new(&invert_path(scramble))
+    };
+
+    // scramble the cube
+    for mv in scramble.iter() {
+        cube.make_move(*mv);
+    }
 
-        // solve for the alternate path
-        let path = solve(&cube, last_move_inv, &pdb_array, scramble_len).expect("No path found :(");
+    // a scramble that cancels itself out (e.g. "R R'") is solved by doing nothing,
+    //  which leaves nothing to show, and any alternate would just be a way of doing nothing
+    if cube.is_solved() {
+        return SolveResult::NoSolution;
+    }
 
-        // the inverse of the solution/path will be the scramble
-        let long_scramble = invert_path(&path);
+    let start = Instant::now();
+    let visited = NodeCounter::new();
+    let budget = Budget {
+        max_nodes: options.max_nodes,
+        time_limit: options.timeout,
+        cancel: None,
+        counter: Some(visited.clone()),
+    };
 
-        // the inverse of the scramble will be the solution
-        let solution = invert_path(&scramble);
+    // solve for the alternate path
+    let length = options.solution_length.policy(scramble_len);
+    let result = if options.stats {
+        let (result, stats) = solve_with_stats(
+            &cube,
+            &constraints,
+            heuristic,
+            length,
+            &budget,
+            options.threads,
+        );
+        print_stats(&stats);
+        result
+    } else {
+        solve(
+            &cube,
+            &constraints,
+            heuristic,
+            length,
+            &budget,
+            options.threads,
+        )
+    };
+    let mut path = match result {
+        SolveResult::Found(path) => path,
+        other => return other,
+    };
 
-        print!("Scramble: ");
-        print_path(&long_scramble);
+    // if the budget runs out while collecting the alternates, pick from the ones found so far
+    //  (there is always at least the one solve found, unless there's no time left at all)
+    if options.random_alternate {
+        let budget = Budget {
+            max_nodes: options
+                .max_nodes
+                .map(|max| max.saturating_sub(visited.get())),
+            time_limit: options
+                .timeout
+                .map(|limit| limit.saturating_sub(start.elapsed())),
+            ..budget
+        };
+        let (alternates, _) = find_solutions(
+            &cube,
+            &constraints,
+            heuristic,
+            path.len() as i32,
+            RANDOM_ALTERNATE_LIMIT,
+            &budget,
+            options.threads,
+        );
+        if !alternates.is_empty() {
+            path = alternates[alternate_rng.random_range(0..alternates.len())].clone();
+        }
+    }
 
-        print!("Press ENTER to see solution");
-        io::stdout().flush().unwrap();
-        let _ = io::stdin().read_line(&mut String::new());
-        println!();
+    // the inverse of the solution/path will be the scramble
+    SolveResult::Found(invert_path(&path))
+}
 
-        print!("Solution: ");
-        print_path(&solution);
+// Print what the search did on every iteration, for --stats
+//  on stderr, so it doesn't get mixed in with the scrambles
+fn print_stats(stats: &SolveStats) {
+    eprintln!(
+        "{:>9} {:>12} {:>12} {:>9}  heuristic wins",
+        "threshold", "expanded", "pruned", "time"
+    );
+    for iteration in &stats.iterations {
+        let wins: Vec<String> = iteration
+            .heuristic_wins
+            .iter()
+            .map(|wins| wins.to_string())
+            .collect();
+        eprintln!(
+            "{:>9} {:>12} {:>12} {:>8.3}s  {}",
+            iteration.threshold,
+            iteration.expanded,
+            iteration.pruned,
+            iteration.elapsed.as_secs_f64(),
+            wins.join(" / ")
+        );
+    }
+    eprintln!(
+        "{:>9} {:>12} {:>12} {:>8.3}s",
+        "total",
+        stats.expanded(),
+        stats.pruned(),
+        stats.elapsed().as_secs_f64()
+    );
+}
 
-        input.clear();
-        print!("\n--------------------\nEnter scramble length (leave empty to quit): ");
-        io::stdout().flush().unwrap();
+// Show the alternate scramble and wait for the user before showing the solution
+fn play_round(
+    scramble: &[Move],
+    heuristic: &impl Heuristic,
+    options: &Options,
+    alternate_rng: &mut StdRng,
+) {
+    let long_scramble = match half_scramble(scramble, heuristic, options, alternate_rng) {
+        SolveResult::Found(long_scramble) => long_scramble,
+        SolveResult::BudgetExceeded(reason) => {
+            println!(
+                "Gave up looking for an alternate scramble ({}), try a shorter one or a longer --timeout",
+                reason
+            );
+            return;
+        }
+        SolveResult::NoSolution => {
+            println!(
+                "No alternate scramble of the requested length and constraints exists, try another one"
+            );
+            return;
+        }
+    };
 
-        io::stdin()
-            .read_line(&mut input)
-            .expect("Failed to read line");
+    // the inverse of the scramble will be the solution
+    let solution = invert_path(scramble);
 
-        println!();
-    }
+    print!("Scramble: ");
+    print_path(&long_scramble);
+
+    print!("Press ENTER to see solution");
+    io::stdout().flush().unwrap();
+    io::stdin()
+        .read_line(&mut String::new())
+        .expect("Failed to read line");
+    println!();
+
+    print!("Solution: ");
+    print_path(&solution);
 }
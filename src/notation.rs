@@ -0,0 +1,286 @@
+use std::fmt;
+
+use crate::cube::{Face, Move};
+
+// Parsing and formatting of standard move notation
+//  "R U R' U' F2" <-> Vec<Move>
+//
+//  Accepted suffixes after a face letter:
+//      (none) -> CW            e.g. R
+//      '      -> CCW           e.g. R'
+//      2      -> double turn   e.g. R2
+//      2'     -> double turn   e.g. R2'  (same as R2)
+//  A backtick (`) or a typographic apostrophe (’) is accepted in place of '
+//  Moves can be seperated by whitespace, commas or nothing at all ("RUR'U'")
+//  Faces have to be upper case, lower case letters are wide turns, which aren't supported
+
+// Everything that can go wrong while parsing
+//  pos is the index of the offending character (in chars, not bytes)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseError {
+    // a character that is not a face letter where a move was expected
+    InvalidFace { pos: usize, found: char },
+    // a modifier with no move right before it, e.g. "' R" or "R '"
+    DanglingModifier { pos: usize, found: char },
+    // more than one prime or number after a move, e.g. "R''" or "R22"
+    RepeatedModifier { pos: usize, found: char },
+    // a number other than 2 after a move, e.g. "R3"
+    InvalidAmount { pos: usize, found: char },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::InvalidFace { pos, found } => {
+                write!(f, "invalid face '{}' at position {}", found, pos)
+            }
+            ParseError::DanglingModifier { pos, found } => {
+                write!(
+                    f,
+                    "modifier '{}' at position {} does not follow a move",
+                    found, pos
+                )
+            }
+            ParseError::RepeatedModifier { pos, found } => {
+                write!(f, "repeated modifier '{}' at position {}", found, pos)
+            }
+            ParseError::InvalidAmount { pos, found } => {
+                write!(
+                    f,
+                    "invalid turn amount '{}' at position {} (only 2 is allowed)",
+                    found, pos
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn parse_face(c: char) -> Option<Face> {
+    match c {
+        'U' => Some(Face::U),
+        'R' => Some(Face::R),
+        'F' => Some(Face::F),
+        'L' => Some(Face::L),
+        'B' => Some(Face::B),
+        'D' => Some(Face::D),
+        _ => None,
+    }
+}
+
+fn is_prime(c: char) -> bool {
+    c == '\'' || c == '`' || c == '’'
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == ','
+}
+
+// Parse a whole scramble, e.g. "R U R' U' F2"
+pub fn parse_path(s: &str) -> Result<Vec<Move>, ParseError> {
+    let mut path: Vec<Move> = Vec::new();
+
+    // modifiers can only be attached to a move until a seperator is hit
+    let mut open = false;
+
+    // keep track of which modifiers the current move already has
+    let mut has_amount = false;
+    let mut has_prime = false;
+
+    for (pos, c) in s.chars().enumerate() {
+        if is_separator(c) {
+            open = false;
+            continue;
+        }
+
+        if let Some(face) = parse_face(c) {
+            path.push(Move { face, coeff: 1 });
+            open = true;
+            has_amount = false;
+            has_prime = false;
+            continue;
+        }
+
+        if !is_prime(c) && !c.is_ascii_digit() {
+            return Err(ParseError::InvalidFace { pos, found: c });
+        }
+
+        // everything after this point modifies the last move
+        let last = match path.last_mut() {
+            Some(mv) if open => mv,
+            _ => return Err(ParseError::DanglingModifier { pos, found: c }),
+        };
+
+        if is_prime(c) {
+            if has_prime {
+                return Err(ParseError::RepeatedModifier { pos, found: c });
+            }
+            has_prime = true;
+
+            // a prime on a double turn does nothing (R2' == R2)
+            if last.coeff != 2 {
+                last.coeff = -1;
+            }
+        } else {
+            if c != '2' {
+                return Err(ParseError::InvalidAmount { pos, found: c });
+            }
+            if has_amount {
+                return Err(ParseError::RepeatedModifier { pos, found: c });
+            }
+            has_amount = true;
+            last.coeff = 2;
+        }
+    }
+
+    Ok(path)
+}
+
+// Format a single move in standard notation
+//  U, U', U2
+pub fn format_move(mv: &Move) -> String {
+    let face = match mv.face {
+        Face::U => 'U',
+        Face::F => 'F',
+        Face::R => 'R',
+        Face::L => 'L',
+        Face::B => 'B',
+        Face::D => 'D',
+    };
+
+    match mv.coeff {
+        -1 => format!("{}'", face),
+        2 => format!("{}2", face),
+        _ => face.to_string(),
+    }
+}
+
+// Format a path as a space seperated string that parse_path can read back
+pub fn format_path(path: &[Move]) -> String {
+    path.iter()
+        .map(format_move)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mv(face: Face, coeff: i8) -> Move {
+        Move { face, coeff }
+    }
+
+    #[test]
+    fn parses_every_suffix() {
+        assert_eq!(
+            parse_path("R U' F2 B2'"),
+            Ok(vec![
+                mv(Face::R, 1),
+                mv(Face::U, -1),
+                mv(Face::F, 2),
+                mv(Face::B, 2),
+            ])
+        );
+    }
+
+    #[test]
+    fn accepts_other_apostrophes() {
+        assert_eq!(
+            parse_path("R` U’"),
+            Ok(vec![mv(Face::R, -1), mv(Face::U, -1)])
+        );
+    }
+
+    #[test]
+    fn accepts_any_separators() {
+        let expected = Ok(vec![
+            mv(Face::R, 1),
+            mv(Face::U, 1),
+            mv(Face::R, -1),
+            mv(Face::U, -1),
+        ]);
+        assert_eq!(parse_path("R U R' U'"), expected);
+        assert_eq!(parse_path("R,U,R',U'"), expected);
+        assert_eq!(parse_path("R, U,\tR'\nU'"), expected);
+        assert_eq!(parse_path("RUR'U'"), expected);
+    }
+
+    #[test]
+    fn empty_is_no_moves() {
+        assert_eq!(parse_path(""), Ok(vec![]));
+        assert_eq!(parse_path(" , "), Ok(vec![]));
+    }
+
+    #[test]
+    fn rejects_lower_case_faces() {
+        assert_eq!(
+            parse_path("R u"),
+            Err(ParseError::InvalidFace { pos: 2, found: 'u' })
+        );
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(
+            parse_path("R X"),
+            Err(ParseError::InvalidFace { pos: 2, found: 'X' })
+        );
+        assert_eq!(
+            parse_path("' R"),
+            Err(ParseError::DanglingModifier {
+                pos: 0,
+                found: '\''
+            })
+        );
+        assert_eq!(
+            parse_path("R '"),
+            Err(ParseError::DanglingModifier {
+                pos: 2,
+                found: '\''
+            })
+        );
+        assert_eq!(
+            parse_path("R U''"),
+            Err(ParseError::RepeatedModifier {
+                pos: 4,
+                found: '\''
+            })
+        );
+        assert_eq!(
+            parse_path("R22"),
+            Err(ParseError::RepeatedModifier { pos: 2, found: '2' })
+        );
+        assert_eq!(
+            parse_path("R U3"),
+            Err(ParseError::InvalidAmount { pos: 3, found: '3' })
+        );
+    }
+
+    #[test]
+    fn error_positions_count_chars_not_bytes() {
+        // ’ is 3 bytes but 1 char
+        assert_eq!(
+            parse_path("R’ X"),
+            Err(ParseError::InvalidFace { pos: 3, found: 'X' })
+        );
+    }
+
+    #[test]
+    fn format_round_trips_to_canonical_form() {
+        let cases = [
+            ("R U R' U'", "R U R' U'"),
+            ("RUR'U'", "R U R' U'"),
+            ("R,U2,F`", "R U2 F'"),
+            ("R2' D’ L", "R2 D' L"),
+            ("  B  ,F2  ", "B F2"),
+        ];
+
+        for (input, canonical) in cases {
+            let path = parse_path(input).unwrap();
+            assert_eq!(format_path(&path), canonical, "formatting {:?}", input);
+            assert_eq!(parse_path(canonical), Ok(path), "reading {:?}", canonical);
+        }
+    }
+}
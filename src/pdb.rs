@@ -1,331 +1,1324 @@
-use crate::cube::{Cube, Face, Move, Piece};
+pub use crate::cube::ALL_MOVES;
+use crate::cube::{Cube, Piece};
+pub use crate::index::PieceKind;
+use crate::index::{PieceSubset, SymmetricSubset};
 
-use std::collections::VecDeque;
+use memmap2::Mmap;
 
-use std::fs::File;
-use std::io::{self, Read};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::thread;
 
 // Compute the actual PDB and return it as a Vec<u8> to be saved
+//  the entries are nibble packed (see get_entry) and indexed by PieceSubset
 //  piece_range: to select which piecies in the piece array to use for the PDB
 //  selector: to select between edge and corner PDBs
 //  orientation_base: (the # of possible orientation states a piece can be in)
-//      to calcualte the state code
+//      has to match the kind of piece the selector picks
+//  threads: how many threads to build with
+//
+// The BFS runs on the indices alone
+//  each level scans the whole table for entries at the current depth, decodes them,
+//  and applies every move to each piece with a per piece move table.
+//  The only memory used is the table itself and a visited bitmap,
+//  and the output is exactly the same no matter how many threads are used.
+//
+// Fails with PDBFileError::TooDeep if a state is further than MAX_PDB_DEPTH from solved
 pub fn build_pdb(
     piece_range: std::ops::Range<usize>,
     selector: fn(&Cube) -> &[Piece],
     orientation_base: usize,
-) -> Vec<u8> {
+    threads: usize,
+) -> Result<Vec<u8>, PDBFileError> {
+    let subset = pdb_subset(piece_range, selector, orientation_base);
+
     // calculate the total size of the PDB
-    let num_pieces = piece_range.end - piece_range.start;
-    let size = factorial_recursive(num_pieces) * orientation_base.pow(7);
+    let num_pieces = subset.num_pieces();
+    let size = subset.size();
+
+    let move_table = piece_move_table(subset.kind, orientation_base);
 
-    // set default to max to more easily identify missed indices
-    let mut pdb = vec![u8::MAX; size];
+    // 2 entries per byte, these are written to from every thread
+    let pdb: Vec<AtomicU8> = (0..packed_len(size)).map(|_| AtomicU8::new(0)).collect();
+    let visited: Vec<AtomicU64> = (0..size.div_ceil(64)).map(|_| AtomicU64::new(0)).collect();
 
     // start with solved cube
-    let solved = Cube::new();
-    let start_index = encode_pieces(selector(&solved), orientation_base, piece_range.clone());
-    pdb[start_index] = 0;
+    let start_index = subset.encode_cube(&Cube::new());
+    claim_index(&visited, start_index);
+
+    // unset entries read as 0 until they are written, so a thread could mistake an entry
+    //  another thread has just claimed for one at depth 0; expanding depth 0 here means
+    //  the threads only ever look for depths an unset entry can't have
+    let mut pieces = [Piece { pos: 0, ori: 0 }; 12];
+    let mut found = expand_index(
+        &subset,
+        &move_table,
+        &pdb,
+        &visited,
+        start_index,
+        0,
+        &mut pieces[..num_pieces],
+    );
+
+    let threads = threads.max(1);
+    let chunk_size = size.div_ceil(threads);
 
-    let mut queue = VecDeque::new();
-    queue.push_back(solved);
+    let mut depth: u8 = 1;
 
-    while let Some(node) = queue.pop_front() {
-        // get depth
-        let depth = pdb[encode_pieces(selector(&node), orientation_base, piece_range.clone())];
+    while found > 0 {
+        // every thread scans its own part of the index space for the current frontier
+        found = thread::scope(|s| {
+            let handles: Vec<_> = (0..threads)
+                .map(|t| {
+                    let (pdb, visited, move_table, subset) = (&pdb, &visited, &move_table, &subset);
+                    s.spawn(move || {
+                        let mut claimed: usize = 0;
+                        let mut pieces = [Piece { pos: 0, ori: 0 }; 12];
 
-        // create all child nodes
-        for mv in ALL_MOVES {
-            let mut new_node = node.clone();
-            new_node.make_move(mv);
+                        for index in (t * chunk_size)..((t + 1) * chunk_size).min(size) {
+                            if !is_visited(visited, index) || get_atomic_entry(pdb, index) != depth
+                            {
+                                continue;
+                            }
+
+                            claimed += expand_index(
+                                subset,
+                                move_table,
+                                pdb,
+                                visited,
+                                index,
+                                depth,
+                                &mut pieces[..num_pieces],
+                            );
+                        }
+
+                        claimed
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("PDB worker thread panicked"))
+                .sum()
+        });
+
+        // anything found from the deepest entries would need a depth that doesn't fit
+        if found > 0 && depth == MAX_PDB_DEPTH {
+            return Err(PDBFileError::TooDeep);
+        }
+
+        depth += 1;
+    }
+
+    Ok(pdb.into_iter().map(|entry| entry.into_inner()).collect())
+}
+
+// Apply every move to the state at 'index' and give each new state depth + 1
+//  pieces: scratch space for the decoded state, as long as the subset
+//  returns how many states were claimed
+fn expand_index(
+    subset: &PieceSubset,
+    move_table: &[[u8; 18]],
+    pdb: &[AtomicU8],
+    visited: &[AtomicU64],
+    index: usize,
+    depth: u8,
+    pieces: &mut [Piece],
+) -> usize {
+    let orientation_base = subset.orientation_base();
+    let mut next = [Piece { pos: 0, ori: 0 }; 12];
+    let next = &mut next[..pieces.len()];
+    let mut claimed = 0;
+
+    subset.decode_into(index, pieces);
+
+    // create all child nodes
+    for (m, _) in ALL_MOVES.iter().enumerate() {
+        for (piece, moved) in pieces.iter().zip(next.iter_mut()) {
+            let coord = piece.pos as usize * orientation_base + piece.ori as usize;
+            let new_coord = move_table[coord][m] as usize;
+            *moved = Piece {
+                pos: (new_coord / orientation_base) as i32,
+                ori: (new_coord % orientation_base) as i32,
+            };
+        }
 
-            // get the index of the new node
-            let index = encode_pieces(selector(&new_node), orientation_base, piece_range.clone());
+        let new_index = subset.encode(next);
 
-            // if index is untouched, change it and add the node to the queue
-            if pdb[index] == u8::MAX {
-                pdb[index] = depth + 1;
-                queue.push_back(new_node);
+        // if index is untouched, change it
+        //  (a state past MAX_PDB_DEPTH is only counted, build_pdb fails because of it)
+        if claim_index(visited, new_index) {
+            if depth < MAX_PDB_DEPTH {
+                set_atomic_entry(pdb, new_index, depth + 1);
             }
+            claimed += 1;
         }
     }
 
-    pdb
+    claimed
 }
 
-// Encode a state into an index to be used in the PDB
-//  pieces: the state of which to be encoded (could be corners or edges)
-//  orientation_base: the # of possible orientations a piece can be in
-//  range: which pieces in the array to encode
-fn encode_pieces(
-    pieces: &[Piece],
+// Reduce a PDB made by build_pdb to one entry per symmetry class (see SymmetricSubset)
+//  the result is a bitmap with a bit set for every index that represents its class,
+//  the rank blocks of the bitmap (see rank_blocks), and the packed entries of just those indices
+//  (the bitmap and rank blocks are little endian u64 words)
+//  threads: how many threads to find the representatives with
+pub fn reduce_pdb(
+    pdb: &[u8],
+    piece_range: std::ops::Range<usize>,
+    selector: fn(&Cube) -> &[Piece],
     orientation_base: usize,
-    range: std::ops::Range<usize>,
-) -> usize {
-    let mut orient_code: usize = 0;
+    threads: usize,
+) -> Vec<u8> {
+    let subset = pdb_subset(piece_range, selector, orientation_base);
+    let symmetric = SymmetricSubset::new(subset.clone());
+
+    let size = subset.size();
+    let num_pieces = subset.num_pieces();
+    let words = size.div_ceil(64);
+
+    let threads = threads.max(1);
+    let chunk_words = words.div_ceil(threads);
+
+    // every thread fills in its own words of the bitmap
+    let bitmap: Vec<u64> = thread::scope(|s| {
+        let handles: Vec<_> = (0..threads)
+            .map(|t| {
+                let (subset, symmetric) = (&subset, &symmetric);
+                s.spawn(move || {
+                    let mut pieces = [Piece { pos: 0, ori: 0 }; 12];
+                    let first = (t * chunk_words).min(words);
+                    let last = ((t + 1) * chunk_words).min(words);
+
+                    (first..last)
+                        .map(|w| {
+                            let mut word: u64 = 0;
+                            for index in (w * 64)..((w + 1) * 64).min(size) {
+                                subset.decode_into(index, &mut pieces[..num_pieces]);
+                                if symmetric.is_representative(index, &pieces[..num_pieces]) {
+                                    word |= 1 << (index % 64);
+                                }
+                            }
+                            word
+                        })
+                        .collect::<Vec<u64>>()
+                })
+            })
+            .collect();
 
-    // The (range.end - 1) ignores the last orientation since it is implied
-    for i in (range.start)..(range.end - 1) {
-        // use base 2 since each piece has 2 orientatinos
-        orient_code = (orient_code * orientation_base) + pieces[i].ori as usize;
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("PDB worker thread panicked"))
+            .collect()
+    });
+
+    let classes: usize = bitmap.iter().map(|word| word.count_ones() as usize).sum();
+
+    let mut entries = vec![0u8; packed_len(classes)];
+    let mut rank: usize = 0;
+    for (w, &word) in bitmap.iter().enumerate() {
+        let mut bits = word;
+        while bits != 0 {
+            let index = w * 64 + bits.trailing_zeros() as usize;
+            set_entry(&mut entries, rank, get_entry(pdb, index));
+            rank += 1;
+            bits &= bits - 1;
+        }
     }
 
-    // Lehmer Code
-    let num_pieces = range.end - range.start;
-    let perm: Vec<u8> = pieces[range].iter().map(|c| c.pos as u8).collect();
-    let mut perm_code: usize = 0;
+    let ranks = rank_blocks(&bitmap);
 
-    for i in 0..num_pieces {
-        // counts the number of items smaller and to the right of perm[i]
-        // that is the Lehmer value for that number
-        let num_smaller = perm[(i + 1)..].iter().filter(|&x| *x < perm[i]).count();
-        perm_code = perm_code * (num_pieces - i) + num_smaller;
+    let mut reduced = Vec::with_capacity(bitmap_len(size) + ranks_len(size) + entries.len());
+    for word in bitmap.into_iter().chain(ranks) {
+        reduced.extend_from_slice(&word.to_le_bytes());
     }
+    reduced.extend_from_slice(&entries);
 
-    // combine permutation and orientation codes for final code
-    perm_code * orientation_base.pow(7) + orient_code
+    reduced
 }
 
-pub struct PDB {
-    data: Box<[u8]>,
+// number of bytes the class bitmap of a reduced PDB takes up
+fn bitmap_len(size: usize) -> usize {
+    size.div_ceil(64) * 8
+}
+
+// The number of representatives before each block of RANK_BLOCK words of a bitmap,
+//  and then the total, so a lookup only has to count the bits in part of one block
+//  these are stored in the file, and checked against the bitmap when a table is loaded
+fn rank_blocks(bitmap: &[u64]) -> Vec<u64> {
+    let mut ranks = Vec::with_capacity(bitmap.len().div_ceil(RANK_BLOCK) + 1);
+    let mut count: u64 = 0;
+    for (w, word) in bitmap.iter().enumerate() {
+        if w % RANK_BLOCK == 0 {
+            ranks.push(count);
+        }
+        count += word.count_ones() as u64;
+    }
+    ranks.push(count);
+    ranks
+}
+
+const RANK_BLOCK: usize = 8;
+
+// number of bytes the rank blocks of a reduced PDB take up
+fn ranks_len(size: usize) -> usize {
+    (size.div_ceil(64).div_ceil(RANK_BLOCK) + 1) * 8
+}
+
+// the nth little endian u64 word of the bitmap or rank blocks
+fn read_word(words: &[u8], word: usize) -> u64 {
+    u64::from_le_bytes(words[word * 8..word * 8 + 8].try_into().unwrap())
+}
+
+// The deepest entry a table can hold, every value of a nibble is a depth (none are reserved)
+pub const MAX_PDB_DEPTH: u8 = 15;
+
+// Entries are stored 2 per byte, the even index in the low nibble
+//  and the odd index in the high nibble
+fn get_entry(data: &[u8], index: usize) -> u8 {
+    (data[index / 2] >> ((index % 2) * 4)) & 0x0F
+}
+
+fn set_entry(data: &mut [u8], index: usize, value: u8) {
+    let shift = (index % 2) * 4;
+    data[index / 2] = (data[index / 2] & !(0x0F << shift)) | (value << shift);
+}
+
+// number of bytes needed to store this many entries
+fn packed_len(entries: usize) -> usize {
+    entries.div_ceil(2)
+}
+
+// Same as get_entry/set_entry, but for tables shared between threads
+//  set_atomic_entry can only be used once per index, since it ORs the value in
+fn get_atomic_entry(data: &[AtomicU8], index: usize) -> u8 {
+    (data[index / 2].load(Ordering::Relaxed) >> ((index % 2) * 4)) & 0x0F
+}
+
+fn set_atomic_entry(data: &[AtomicU8], index: usize, value: u8) {
+    data[index / 2].fetch_or(value << ((index % 2) * 4), Ordering::Relaxed);
+}
+
+fn is_visited(visited: &[AtomicU64], index: usize) -> bool {
+    visited[index / 64].load(Ordering::Relaxed) & (1 << (index % 64)) != 0
+}
+
+// Mark an index as visited, returns false if it already was
+fn claim_index(visited: &[AtomicU64], index: usize) -> bool {
+    let bit = 1 << (index % 64);
+    visited[index / 64].fetch_or(bit, Ordering::Relaxed) & bit == 0
+}
+
+// For every (position, orientation) of a single piece, where it ends up after each move
+//  indexed by [pos * orientation_base + ori][move], in the same order as ALL_MOVES
+fn piece_move_table(kind: PieceKind, orientation_base: usize) -> Vec<[u8; 18]> {
+    let num_slots = kind.num_slots();
+    let mut table = vec![[0u8; 18]; num_slots * orientation_base];
+
+    for pos in 0..num_slots {
+        for ori in 0..orientation_base {
+            for (m, mv) in ALL_MOVES.iter().enumerate() {
+                // track the piece that starts in 'pos' through the move
+                let mut cube = Cube::new();
+                kind.pieces_mut(&mut cube)[pos].ori = ori as i32;
+                cube.make_move(*mv);
+
+                let piece = kind.pieces_mut(&mut cube)[pos];
+                table[pos * orientation_base + ori][m] =
+                    (piece.pos as usize * orientation_base + piece.ori as usize) as u8;
+            }
+        }
+    }
+
+    table
+}
+
+// The subset a PDB is indexed by
+fn pdb_subset(
     range: std::ops::Range<usize>,
     selector: fn(&Cube) -> &[Piece],
     orientation_base: usize,
+) -> PieceSubset {
+    let subset = PieceSubset::new(PieceKind::of(selector), range);
+    assert_eq!(
+        subset.orientation_base(),
+        orientation_base,
+        "Wrong orientation base for {:?}",
+        subset.kind
+    );
+    subset
+}
+
+// Where the entries of a PDB live
+enum PDBData {
+    // read into memory
+    Owned(Box<[u8]>),
+    // mapped straight from the file (the header is skipped when reading)
+    Mapped(Mmap),
+}
+
+impl PDBData {
+    fn entries(&self) -> &[u8] {
+        match self {
+            PDBData::Owned(data) => data,
+            PDBData::Mapped(map) => &map[HEADER_LEN..],
+        }
+    }
+}
+
+// What a symmetry reduced PDB needs to find the entry of a class
+struct ClassLookup {
+    symmetric: SymmetricSubset,
+    // where the rank blocks start (after the bitmap)
+    ranks_start: usize,
+    // where the entries start (after the rank blocks)
+    entries_start: usize,
+}
+
+impl ClassLookup {
+    // Every rank block is checked against the bits of the bitmap before it, and the total
+    //  against 'classes', so a corrupt block can't send a lookup past the entries
+    //  (mapped tables skip the checksum, so this is all that catches it there)
+    fn new(
+        symmetric: SymmetricSubset,
+        data: &[u8],
+        classes: u64,
+    ) -> Result<ClassLookup, PDBFileError> {
+        let size = symmetric.subset.size();
+        let ranks_start = bitmap_len(size);
+        let words = size.div_ceil(64);
+        let ranks = &data[ranks_start..];
+
+        let mut count: u64 = 0;
+        for w in 0..words {
+            if w % RANK_BLOCK == 0 && read_word(ranks, w / RANK_BLOCK) != count {
+                return Err(PDBFileError::BadRankBlock(w / RANK_BLOCK));
+            }
+            count += read_word(data, w).count_ones() as u64;
+        }
+
+        let total = read_word(ranks, ranks_len(size) / 8 - 1);
+        if total != count || total != classes {
+            return Err(PDBFileError::WrongClassCount(classes));
+        }
+
+        Ok(ClassLookup {
+            symmetric,
+            ranks_start,
+            entries_start: ranks_start + ranks_len(size),
+        })
+    }
+
+    // the position of a representative's entry, the number of representatives before it
+    fn rank(&self, data: &[u8], index: usize) -> usize {
+        let word = index / 64;
+        let block = word / RANK_BLOCK;
+
+        let mut rank = read_word(&data[self.ranks_start..], block) as usize;
+        for w in (block * RANK_BLOCK)..word {
+            rank += read_word(data, w).count_ones() as usize;
+        }
+
+        let below = (1u64 << (index % 64)) - 1;
+        rank + (read_word(data, word) & below).count_ones() as usize
+    }
+
+    fn get_entry(&self, data: &[u8], representative: usize) -> u8 {
+        get_entry(&data[self.entries_start..], self.rank(data, representative))
+    }
+}
+
+pub struct PDB {
+    data: PDBData,
+    subset: PieceSubset,
+    // only for symmetry reduced tables
+    classes: Option<ClassLookup>,
 }
 
 impl PDB {
     // Get the heuristic value for a certain cube state
     pub fn get_heuristic(&self, cube: &Cube) -> i32 {
-        // collect the slice of Pieces using the selector
-        //      Either corners or edges
-        let pieces: &[Piece] = (self.selector)(cube);
+        match &self.classes {
+            None => get_entry(self.data.entries(), self.subset.encode_cube(cube)) as i32,
+            Some(classes) => classes.get_entry(
+                self.data.entries(),
+                classes.symmetric.representative_cube(cube),
+            ) as i32,
+        }
+    }
+
+    // Get the entry at an index (see PieceSubset::encode)
+    pub fn get_entry(&self, index: usize) -> u8 {
+        match &self.classes {
+            None => get_entry(self.data.entries(), index),
+            Some(classes) => {
+                let mut pieces = [Piece { pos: 0, ori: 0 }; 12];
+                let pieces = &mut pieces[..self.subset.num_pieces()];
+                self.subset.decode_into(index, pieces);
+
+                classes.get_entry(
+                    self.data.entries(),
+                    classes.symmetric.representative(pieces),
+                )
+            }
+        }
+    }
+
+    // true if the table only has an entry per symmetry class
+    pub fn is_reduced(&self) -> bool {
+        self.classes.is_some()
+    }
 
-        let index: usize = encode_pieces(pieces, self.orientation_base, self.range.clone());
+    // Put together a PDB from its entries, setting up the class lookup if it is reduced
+    fn from_data(data: PDBData, header: &PDBHeader, subset: PieceSubset) -> io::Result<PDB> {
+        let classes = if header.reduced {
+            Some(ClassLookup::new(
+                SymmetricSubset::new(subset.clone()),
+                data.entries(),
+                header.entries,
+            )?)
+        } else {
+            None
+        };
 
-        self.data[index] as i32
+        Ok(PDB {
+            data,
+            subset,
+            classes,
+        })
     }
 
-    // Initialize a new PDB
+    // The pieces this PDB is indexed by
+    pub fn subset(&self) -> &PieceSubset {
+        &self.subset
+    }
+
+    // Initialize a new PDB by reading the whole file into memory
     pub fn new<P: AsRef<Path>>(
         in_path: P,
         in_range: std::ops::Range<usize>,
         in_selector: fn(&Cube) -> &[Piece],
         in_base: usize,
     ) -> io::Result<PDB> {
-        let pdb = PDB {
-            data: load_pdb(in_path, in_base, in_range.end - in_range.start)?.into_boxed_slice(),
-            range: in_range,
-            selector: in_selector,
-            orientation_base: in_base,
-        };
+        let (header, data) = load_pdb(in_path, in_range.clone(), in_selector, in_base)?;
 
-        Ok(pdb)
+        PDB::from_data(
+            PDBData::Owned(data.into_boxed_slice()),
+            &header,
+            pdb_subset(in_range, in_selector, in_base),
+        )
     }
-}
 
-// Gets the largest heuristic from a slice of PDBs for a given state
-pub fn get_max_heuristic(cube: &Cube, pdbs: &[PDB]) -> i32 {
-    pdbs.iter()
-        .map(|pdb| pdb.get_heuristic(cube))
-        .max()
-        .expect("Unable to retreave heuristic")
+    // Initialize a new PDB by memory mapping the file
+    //  this is near instant and the pages are shared between processes using the same file
+    //  the header and size are checked, but the checksum is not (that would read every page)
+    //  the rank blocks of a reduced table are checked against its bitmap (see ClassLookup::new)
+    //  falls back to PDB::new if the file can't be mapped
+    pub fn map<P: AsRef<Path>>(
+        in_path: P,
+        in_range: std::ops::Range<usize>,
+        in_selector: fn(&Cube) -> &[Piece],
+        in_base: usize,
+    ) -> io::Result<PDB> {
+        match map_pdb(&in_path, in_range.clone(), in_selector, in_base)? {
+            Some((header, map)) => PDB::from_data(
+                PDBData::Mapped(map),
+                &header,
+                pdb_subset(in_range, in_selector, in_base),
+            ),
+            None => PDB::new(in_path, in_range, in_selector, in_base),
+        }
+    }
+
+    // true if the entries are memory mapped rather than owned
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.data, PDBData::Mapped(_))
+    }
 }
 
-// Load PDB from a given path
-fn load_pdb<P: AsRef<Path>>(
-    path: P,
-    orientation_base: usize,
-    num_pieces: usize,
-) -> io::Result<Vec<u8>> {
-    let mut f = File::open(path)?;
+// vvv Table specs vvv
+//
+// The solver uses a corner table and 2 edge tables.
+//  The edge tables are partial permutations of 6, 7 or 8 of the 12 edges
+//  (12P6 * 2^6, 12P7 * 2^7 or 12P8 * 2^8 entries), indexed by PieceSubset like the corners.
 
-    let mut pdb =
-        vec![0u8; factorial_recursive(num_pieces) * orientation_base.pow((num_pieces - 1) as u32)];
+// Everything needed to build or load one of the PDBs the solver uses
+pub struct PDBSpec {
+    pub name: &'static str,
+    pub file_name: &'static str,
+    pub range: std::ops::Range<usize>,
+    pub selector: fn(&Cube) -> &[Piece],
+    pub orientation_base: usize,
+}
 
-    f.read_exact(&mut pdb)?;
+fn select_corners(cube: &Cube) -> &[Piece] {
+    &cube.corners
+}
 
-    Ok(pdb)
+fn select_edges(cube: &Cube) -> &[Piece] {
+    &cube.edges
 }
 
-// returns (number!)
-fn factorial_recursive(number: usize) -> usize {
-    // Base Case
-    if number <= 1 {
-        return 1;
+// How many edges each of the 2 edge tables covers
+//  bigger tables give a stronger heuristic but take a lot more memory:
+//      6 edges: 2 x 42.6M entries (21 MB each)
+//      7 edges: 2 x 511M entries (255 MB each)
+//      8 edges: 2 x 5.1G entries (2.6 GB each)
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum EdgeTables {
+    #[default]
+    Six,
+    Seven,
+    Eight,
+}
+
+impl EdgeTables {
+    pub fn from_size(size: usize) -> Option<EdgeTables> {
+        match size {
+            6 => Some(EdgeTables::Six),
+            7 => Some(EdgeTables::Seven),
+            8 => Some(EdgeTables::Eight),
+            _ => None,
+        }
     }
 
-    // Recursive Case
-    return number * factorial_recursive(number - 1);
+    pub fn size(self) -> usize {
+        match self {
+            EdgeTables::Six => 6,
+            EdgeTables::Seven => 7,
+            EdgeTables::Eight => 8,
+        }
+    }
 }
 
-// array of all possible moves
-pub const ALL_MOVES: [Move; 18] = [
-    Move {
-        face: Face::U,
-        coeff: -1,
-    },
-    Move {
-        face: Face::U,
-        coeff: 1,
-    },
-    Move {
-        face: Face::U,
-        coeff: 2,
-    },
-    Move {
-        face: Face::F,
-        coeff: -1,
-    },
-    Move {
-        face: Face::F,
-        coeff: 1,
-    },
-    Move {
-        face: Face::F,
-        coeff: 2,
-    },
-    Move {
-        face: Face::R,
-        coeff: -1,
-    },
-    Move {
-        face: Face::R,
-        coeff: 1,
-    },
-    Move {
-        face: Face::R,
-        coeff: 2,
-    },
-    Move {
-        face: Face::L,
-        coeff: -1,
+const CORNER_SPEC: PDBSpec = PDBSpec {
+    name: "corner PDB",
+    file_name: "corner_pdb.bin",
+    range: 0..8,
+    selector: select_corners,
+    orientation_base: 3,
+};
+
+// the 6 edge tables split the edges in half
+const EDGE6_SPECS: [PDBSpec; 2] = [
+    PDBSpec {
+        name: "edge PDB #1",
+        file_name: "edge_pdb_1.bin",
+        range: 0..6,
+        selector: select_edges,
+        orientation_base: 2,
     },
-    Move {
-        face: Face::L,
-        coeff: 1,
+    PDBSpec {
+        name: "edge PDB #2",
+        file_name: "edge_pdb_2.bin",
+        range: 6..12,
+        selector: select_edges,
+        orientation_base: 2,
     },
-    Move {
-        face: Face::L,
-        coeff: 2,
+];
+
+// the 7 and 8 edge tables overlap in the middle
+const EDGE7_SPECS: [PDBSpec; 2] = [
+    PDBSpec {
+        name: "7-edge PDB #1",
+        file_name: "edge7_pdb_1.bin",
+        range: 0..7,
+        selector: select_edges,
+        orientation_base: 2,
     },
-    Move {
-        face: Face::B,
-        coeff: -1,
+    PDBSpec {
+        name: "7-edge PDB #2",
+        file_name: "edge7_pdb_2.bin",
+        range: 5..12,
+        selector: select_edges,
+        orientation_base: 2,
     },
-    Move {
-        face: Face::B,
-        coeff: 1,
+];
+
+const EDGE8_SPECS: [PDBSpec; 2] = [
+    PDBSpec {
+        name: "8-edge PDB #1",
+        file_name: "edge8_pdb_1.bin",
+        range: 0..8,
+        selector: select_edges,
+        orientation_base: 2,
     },
-    Move {
-        face: Face::B,
-        coeff: 2,
+    PDBSpec {
+        name: "8-edge PDB #2",
+        file_name: "edge8_pdb_2.bin",
+        range: 4..12,
+        selector: select_edges,
+        orientation_base: 2,
     },
-    Move {
-        face: Face::D,
-        coeff: -1,
+];
+
+// The tables used by the solver, the corners and 2 edge tables
+//  the position in this array (+1) is the number passed to build_pdb
+pub fn pdb_specs(edges: EdgeTables) -> [PDBSpec; 3] {
+    let [edge1, edge2] = match edges {
+        EdgeTables::Six => EDGE6_SPECS,
+        EdgeTables::Seven => EDGE7_SPECS,
+        EdgeTables::Eight => EDGE8_SPECS,
+    };
+
+    [CORNER_SPEC, edge1, edge2]
+}
+
+// vvv File Format vvv
+//
+// Every PDB file starts with a 32 byte header (little endian) followed by the entries
+//  symmetry reduced tables have the class bitmap and its rank blocks before the entries
+//  (see reduce_pdb)
+//   0..8    magic ("HSPDB" + 3 zero bytes)
+//   8..10   format version
+//   10      piece kind (0 = corners, 1 = edges)
+//   11      first piece in the range
+//   12      end of the range (exclusive)
+//   13      orientation base
+//   14      entry encoding (always 1, two entries per byte)
+//   15      1 if the table is symmetry reduced, 0 if it has an entry for every index
+//   16..24  number of entries (the number of classes for reduced tables)
+//   24..32  checksum of the entries (FNV-1a 64)
+
+const PDB_MAGIC: [u8; 8] = *b"HSPDB\0\0\0";
+const PDB_VERSION: u16 = 1;
+const HEADER_LEN: usize = 32;
+// the entries are nibble packed (see get_entry)
+const ENTRY_ENCODING: u8 = 1;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PDBHeader {
+    pub kind: PieceKind,
+    pub range: std::ops::Range<usize>,
+    pub orientation_base: usize,
+    pub reduced: bool,
+    pub entries: u64,
+    pub checksum: u64,
+}
+
+// Everything that can be wrong with a PDB file
+#[derive(Clone, PartialEq, Debug)]
+pub enum PDBFileError {
+    // not a PDB, or built before the header was added
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnknownPieceKind(u8),
+    UnknownEncoding(u8),
+    // the file is a valid PDB but not the one that was asked for
+    Mismatch {
+        expected: PDBHeader,
+        found: PDBHeader,
     },
-    Move {
-        face: Face::D,
-        coeff: 1,
+    // the file has fewer or more bytes of entries than the header says
+    WrongSize {
+        expected: u64,
+        found: u64,
     },
-    Move {
-        face: Face::D,
-        coeff: 2,
+    BadChecksum {
+        expected: u64,
+        found: u64,
     },
-];
+    // the rank blocks of a reduced table don't add up to as many classes as the header says
+    WrongClassCount(u64),
+    // a rank block of a reduced table doesn't match the bitmap before it
+    BadRankBlock(usize),
+    // a state is too far from solved for its depth to fit in an entry
+    TooDeep,
+}
 
-/*
-// decode the orientaions from the code
-fn rev_corner_orientations(mut code: usize) -> [u8; 8] {
-    let mut orientations: [u8; 8] = [0; 8];
+impl fmt::Display for PDBFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PDBFileError::BadMagic => write!(
+                f,
+                "not a PDB file (it may have been built by an older version, rebuild it)"
+            ),
+            PDBFileError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported PDB format version {} (expected {}), rebuild it",
+                version, PDB_VERSION
+            ),
+            PDBFileError::UnknownPieceKind(kind) => write!(f, "unknown piece kind {}", kind),
+            PDBFileError::UnknownEncoding(encoding) => {
+                write!(f, "unknown entry encoding {}", encoding)
+            }
+            PDBFileError::Mismatch { expected, found } => write!(
+                f,
+                "wrong PDB: expected {:?} {:?} with orientation base {} ({} entries), \
+                found {:?} {:?} with orientation base {} ({} entries)",
+                expected.kind,
+                expected.range,
+                expected.orientation_base,
+                expected.entries,
+                found.kind,
+                found.range,
+                found.orientation_base,
+                found.entries
+            ),
+            PDBFileError::WrongSize { expected, found } => write!(
+                f,
+                "PDB is truncated or corrupt: expected {} bytes of entries, found {}",
+                expected, found
+            ),
+            PDBFileError::BadChecksum { expected, found } => write!(
+                f,
+                "PDB is corrupt: checksum is {:016x}, expected {:016x}",
+                found, expected
+            ),
+            PDBFileError::WrongClassCount(classes) => write!(
+                f,
+                "PDB is corrupt: its rank blocks don't add up to {} classes",
+                classes
+            ),
+            PDBFileError::BadRankBlock(block) => write!(
+                f,
+                "PDB is corrupt: rank block {} doesn't match its bitmap",
+                block
+            ),
+            PDBFileError::TooDeep => write!(
+                f,
+                "PDB has states deeper than {} moves, which don't fit in an entry",
+                MAX_PDB_DEPTH
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PDBFileError {}
 
-    for i in (0..7).rev() {
-        orientations[i] = (code % 3) as u8;
-        code /= 3;
+impl From<PDBFileError> for io::Error {
+    fn from(e: PDBFileError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, e)
     }
+}
 
-    // Solve for the last orientation
-    // For any valid position, the following equality is true
-    //      0 = orientation_sum % 3
-    //  Where orientation_sum is the sum of the orientations of the pieces
+impl PDBHeader {
+    // the header a PDB with these parameters should have (checksum not filled in)
+    fn expected(
+        range: std::ops::Range<usize>,
+        selector: fn(&Cube) -> &[Piece],
+        orientation_base: usize,
+    ) -> PDBHeader {
+        let subset = pdb_subset(range, selector, orientation_base);
+        PDBHeader {
+            kind: subset.kind,
+            entries: subset.size() as u64,
+            range: subset.range,
+            orientation_base,
+            reduced: false,
+            checksum: 0,
+        }
+    }
 
-    let sum: u8 = orientations.iter().copied().sum();
-    orientations[7] = (3 - (sum % 3)) % 3;
+    // checks everything but the checksum
+    //  (and the number of entries if either is reduced, that depends on the table)
+    fn matches(&self, other: &PDBHeader) -> bool {
+        self.kind == other.kind
+            && self.range == other.range
+            && self.orientation_base == other.orientation_base
+            && (self.reduced || other.reduced || self.entries == other.entries)
+    }
 
-    orientations
-}
+    // number of bytes the entries (and bitmap and rank blocks) take up on disk
+    //  only valid once the header matches, since the bitmap size depends on the range
+    fn data_len(&self) -> u64 {
+        let bitmap = if self.reduced {
+            let size = PieceSubset::new(self.kind, self.range.clone()).size();
+            (bitmap_len(size) + ranks_len(size)) as u64
+        } else {
+            0
+        };
 
-// decode the positions from the code
-fn rev_corner_lehmer(mut code: usize) -> [u8; 8] {
-    let mut positions: [u8; 8] = [0; 8];
+        bitmap + packed_len(self.entries as usize) as u64
+    }
 
-    // the lehmer number for the 8th piece is always 0
-    for i in (0..7).rev() {
-        let num_smaller = (code % (8 - i)) as u8;
+    fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
 
-        for j in &mut positions[i + 1..] {
-            if *j >= num_smaller {
-                *j += 1;
-            }
+        bytes[0..8].copy_from_slice(&PDB_MAGIC);
+        bytes[8..10].copy_from_slice(&PDB_VERSION.to_le_bytes());
+        bytes[10] = self.kind as u8;
+        bytes[11] = self.range.start as u8;
+        bytes[12] = self.range.end as u8;
+        bytes[13] = self.orientation_base as u8;
+        bytes[14] = ENTRY_ENCODING;
+        bytes[15] = self.reduced as u8;
+        bytes[16..24].copy_from_slice(&self.entries.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.checksum.to_le_bytes());
+
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; HEADER_LEN]) -> Result<PDBHeader, PDBFileError> {
+        if bytes[0..8] != PDB_MAGIC {
+            return Err(PDBFileError::BadMagic);
         }
 
-        positions[i] = num_smaller;
+        let version = u16::from_le_bytes([bytes[8], bytes[9]]);
+        if version != PDB_VERSION {
+            return Err(PDBFileError::UnsupportedVersion(version));
+        }
 
-        code = code / (8 - i);
+        if bytes[14] != ENTRY_ENCODING {
+            return Err(PDBFileError::UnknownEncoding(bytes[14]));
+        }
+
+        Ok(PDBHeader {
+            kind: PieceKind::from_u8(bytes[10]).ok_or(PDBFileError::UnknownPieceKind(bytes[10]))?,
+            range: (bytes[11] as usize)..(bytes[12] as usize),
+            orientation_base: bytes[13] as usize,
+            reduced: bytes[15] != 0,
+            entries: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+            checksum: u64::from_le_bytes(bytes[24..32].try_into().unwrap()),
+        })
     }
+}
 
-    positions
+// FNV-1a, just to catch corrupt or truncated files
+fn checksum(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in data {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
 }
 
-fn decode_corners(index: usize) -> [Piece; 8] {
-    // split code
-    let orient_code = index % 2187;
-    let perm_code = index / 2187; // this truncates the orient_code part off
+// Read just the header of a PDB file
+pub fn read_pdb_header<P: AsRef<Path>>(path: P) -> io::Result<PDBHeader> {
+    let mut f = File::open(path)?;
+
+    let mut bytes = [0u8; HEADER_LEN];
+    f.read_exact(&mut bytes).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => PDBFileError::BadMagic.into(),
+        _ => e,
+    })?;
 
-    // decode everything
-    let orientations: [u8; 8] = rev_corner_orientations(orient_code);
-    let positions: [u8; 8] = rev_corner_lehmer(perm_code);
+    Ok(PDBHeader::from_bytes(&bytes)?)
+}
 
-    // Complete the pieces
-    let mut pieces: [Piece; 8] = [Piece { pos: 0, ori: 0 }; 8];
+// Save a PDB made by build_pdb along with its header
+pub fn save_pdb<P: AsRef<Path>>(
+    path: P,
+    pdb: &[u8],
+    range: std::ops::Range<usize>,
+    selector: fn(&Cube) -> &[Piece],
+    orientation_base: usize,
+) -> io::Result<()> {
+    let mut header = PDBHeader::expected(range, selector, orientation_base);
+    header.checksum = checksum(pdb);
+
+    write_pdb(path, &header, pdb)
+}
 
-    for i in 0..8 {
-        pieces[i].ori = orientations[i] as i32;
-        pieces[i].pos = positions[i] as i32;
+// Save a PDB made by reduce_pdb along with its header
+pub fn save_reduced_pdb<P: AsRef<Path>>(
+    path: P,
+    reduced: &[u8],
+    range: std::ops::Range<usize>,
+    selector: fn(&Cube) -> &[Piece],
+    orientation_base: usize,
+) -> io::Result<()> {
+    let mut header = PDBHeader::expected(range, selector, orientation_base);
+    let bitmap = &reduced[..bitmap_len(header.entries as usize).min(reduced.len())];
+
+    header.reduced = true;
+    header.entries = bitmap.iter().map(|byte| byte.count_ones() as u64).sum();
+    header.checksum = checksum(reduced);
+
+    write_pdb(path, &header, reduced)
+}
+
+fn write_pdb(path: impl AsRef<Path>, header: &PDBHeader, data: &[u8]) -> io::Result<()> {
+    if data.len() as u64 != header.data_len() {
+        return Err(PDBFileError::WrongSize {
+            expected: header.data_len(),
+            found: data.len() as u64,
+        }
+        .into());
+    }
+
+    // write to a temporary file and rename it over the old one,
+    //  so a solver that has the old file mapped keeps seeing the old (complete) table
+    let path = path.as_ref();
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = Path::new(&tmp_name);
+
+    let mut f = File::create(tmp_path)?;
+    f.write_all(&header.to_bytes())?;
+    f.write_all(data)?;
+    f.sync_all()?;
+
+    fs::rename(tmp_path, path)
+}
+
+// Memory map a PDB file after checking its header and size
+//  returns None if the file can't be mapped and has to be loaded with load_pdb
+fn map_pdb<P: AsRef<Path>>(
+    path: P,
+    range: std::ops::Range<usize>,
+    selector: fn(&Cube) -> &[Piece],
+    orientation_base: usize,
+) -> io::Result<Option<(PDBHeader, Mmap)>> {
+    let found = read_pdb_header(&path)?;
+    let expected = PDBHeader::expected(range, selector, orientation_base);
+
+    if !found.matches(&expected) {
+        return Err(PDBFileError::Mismatch { expected, found }.into());
     }
 
-    pieces
+    let f = File::open(path)?;
+
+    // SAFETY: Mmap::map is unsafe because another process could change or truncate the file
+    //  while it is mapped. This program never does: write_pdb writes a new file and renames
+    //  it over the old one, so the mapping keeps the old inode.
+    //  Anything else writing to the file in place is not guarded against. The entries are
+    //  plain bytes with no invariants, so a rewrite can only give wrong estimates (which
+    //  only makes the search slower or the alternate longer), but a truncation makes
+    //  lookups past the new end fault with SIGBUS. We accept that for files in our own
+    //  PDB directory; --no-mmap reads the tables into memory instead
+    let map = match unsafe { Mmap::map(&f) } {
+        Ok(map) => map,
+        Err(_) => return Ok(None),
+    };
+
+    let data_len = map.len().saturating_sub(HEADER_LEN) as u64;
+    if data_len != found.data_len() {
+        return Err(PDBFileError::WrongSize {
+            expected: found.data_len(),
+            found: data_len,
+        }
+        .into());
+    }
+
+    Ok(Some((found, map)))
 }
 
-pub fn test_encode_decode(test_len: i32) -> bool {
-    let mut rng = rand::rng();
+// Load PDB from a given path
+//  the header is checked against what the caller expects, and the entries against the checksum
+fn load_pdb<P: AsRef<Path>>(
+    path: P,
+    range: std::ops::Range<usize>,
+    selector: fn(&Cube) -> &[Piece],
+    orientation_base: usize,
+) -> io::Result<(PDBHeader, Vec<u8>)> {
+    let found = read_pdb_header(&path)?;
+    let expected = PDBHeader::expected(range, selector, orientation_base);
 
-    let mut cube: Cube = Cube::new();
+    if !found.matches(&expected) {
+        return Err(PDBFileError::Mismatch { expected, found }.into());
+    }
 
-    for _ in 0..test_len {
-        let mv = ALL_MOVES[rng.random_range(0..18)];
-        cube.make_move(mv);
+    let mut f = File::open(path)?;
+    f.seek(SeekFrom::Start(HEADER_LEN as u64))?;
 
-        let code = encode_corners(&cube.corners);
+    let mut pdb = Vec::with_capacity(found.data_len() as usize);
+    f.read_to_end(&mut pdb)?;
 
-        let decoded_corners = decode_corners(code);
+    if pdb.len() as u64 != found.data_len() {
+        return Err(PDBFileError::WrongSize {
+            expected: found.data_len(),
+            found: pdb.len() as u64,
+        }
+        .into());
+    }
 
-        if are_equal_corners(cube.corners, decoded_corners) == false {
-            return false;
+    let sum = checksum(&pdb);
+    if sum != found.checksum {
+        return Err(PDBFileError::BadChecksum {
+            expected: found.checksum,
+            found: sum,
         }
+        .into());
     }
 
-    true
+    Ok((found, pdb))
 }
 
-fn are_equal_corners(c1: [Piece; 8], c2: [Piece; 8]) -> bool {
-    for i in 0..8 {
-        if c1[i].pos != c2[i].pos || c1[i].ori != c2[i].ori {
-            return false;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, VecDeque};
+    use std::fs::OpenOptions;
+    use std::path::PathBuf;
+
+    // a table small enough to build in every test
+    const SMALL_CORNERS: std::ops::Range<usize> = 0..3;
+
+    // a file in the temp directory, unique to this test run
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("halfscramble_{}_{}.bin", std::process::id(), name))
+    }
+
+    // the PDBFileError behind a failed load
+    fn file_error(result: io::Result<PDB>) -> PDBFileError {
+        match result {
+            Ok(_) => panic!("the PDB should not have loaded"),
+            Err(e) => e
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<PDBFileError>())
+                .unwrap_or_else(|| panic!("not a PDBFileError: {}", e))
+                .clone(),
+        }
+    }
+
+    fn save_small_corners(name: &str) -> (PathBuf, Vec<u8>) {
+        let pdb = build_pdb(SMALL_CORNERS, select_corners, 3, 1).unwrap();
+        let path = temp_path(name);
+        save_pdb(&path, &pdb, SMALL_CORNERS, select_corners, 3).unwrap();
+        (path, pdb)
+    }
+
+    fn load_small_corners(path: &Path) -> io::Result<PDB> {
+        PDB::new(path, SMALL_CORNERS, select_corners, 3)
+    }
+
+    // The depth of every state of a subset, found with a plain BFS over whole cubes
+    fn cube_bfs(subset: &PieceSubset) -> HashMap<usize, u8> {
+        let mut depths = HashMap::new();
+        let mut queue = VecDeque::new();
+
+        depths.insert(subset.encode_cube(&Cube::new()), 0);
+        queue.push_back((Cube::new(), 0));
+
+        while let Some((cube, depth)) = queue.pop_front() {
+            for mv in ALL_MOVES {
+                let mut next = cube;
+                next.make_move(mv);
+                depths.entry(subset.encode_cube(&next)).or_insert_with(|| {
+                    queue.push_back((next, depth + 1));
+                    depth + 1
+                });
+            }
+        }
+
+        depths
+    }
+
+    // build a table with each number of threads and check it against cube_bfs
+    fn check_against_cube_bfs(
+        range: std::ops::Range<usize>,
+        selector: fn(&Cube) -> &[Piece],
+        orientation_base: usize,
+        threads: &[usize],
+    ) {
+        let subset = pdb_subset(range.clone(), selector, orientation_base);
+        let depths = cube_bfs(&subset);
+        assert_eq!(depths.len(), subset.size());
+
+        for &threads in threads {
+            let pdb = build_pdb(range.clone(), selector, orientation_base, threads).unwrap();
+            assert_eq!(pdb.len(), packed_len(subset.size()));
+            for (&index, &depth) in &depths {
+                assert_eq!(
+                    get_entry(&pdb, index),
+                    depth,
+                    "index {} of {:?} with {} threads",
+                    index,
+                    range,
+                    threads
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn build_pdb_is_the_same_on_any_threads() {
+        check_against_cube_bfs(SMALL_CORNERS, select_corners, 3, &[1, 4]);
+        check_against_cube_bfs(0..4, select_edges, 2, &[1, 4]);
+    }
+
+    // the subsets that don't start at the first piece, on more threads than the BFS
+    //  has frontier states for at first (each thread only gets a few hundred indices),
+    //  which is when a thread could pick up an entry before it was written
+    #[test]
+    fn index_bfs_matches_cube_bfs() {
+        check_against_cube_bfs(5..8, select_corners, 3, &[16]);
+        check_against_cube_bfs(8..12, select_edges, 2, &[64]);
+    }
+
+    #[test]
+    fn piece_move_table_matches_make_move() {
+        let mut cube = Cube::new();
+        for (kind, orientation_base) in [(PieceKind::Corners, 3), (PieceKind::Edges, 2)] {
+            let table = piece_move_table(kind, orientation_base);
+
+            // go through a few states, so the pieces aren't all at orientation 0
+            for &scramble in ALL_MOVES.iter().step_by(4) {
+                cube.make_move(scramble);
+
+                for (m, mv) in ALL_MOVES.iter().enumerate() {
+                    let mut moved = cube;
+                    moved.make_move(*mv);
+
+                    let pieces = kind.pieces(&cube).iter().zip(kind.pieces(&moved));
+                    for (piece, moved_piece) in pieces {
+                        let coord = piece.pos as usize * orientation_base + piece.ori as usize;
+                        let moved_coord =
+                            moved_piece.pos as usize * orientation_base + moved_piece.ori as usize;
+                        assert_eq!(table[coord][m] as usize, moved_coord);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn saved_pdbs_load_the_same() {
+        let (path, pdb) = save_small_corners("round_trip");
+
+        let header = read_pdb_header(&path).unwrap();
+        assert_eq!(header.kind, PieceKind::Corners);
+        assert_eq!(header.range, SMALL_CORNERS);
+        assert_eq!(header.orientation_base, 3);
+        assert_eq!(header.entries, (8 * 7 * 6 * 27) as u64);
+        assert_eq!(header.checksum, checksum(&pdb));
+
+        let loaded = load_small_corners(&path).unwrap();
+        for index in 0..header.entries as usize {
+            assert_eq!(loaded.get_entry(index), get_entry(&pdb, index));
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn mapped_pdbs_match_loaded_ones() {
+        let (path, pdb) = save_small_corners("mapped");
+        let reduced_path = temp_path("mapped_reduced");
+        let reduced = reduce_pdb(&pdb, SMALL_CORNERS, select_corners, 3, 2);
+        save_reduced_pdb(&reduced_path, &reduced, SMALL_CORNERS, select_corners, 3).unwrap();
+
+        for path in [&path, &reduced_path] {
+            let loaded = load_small_corners(path).unwrap();
+            let mapped = PDB::map(path, SMALL_CORNERS, select_corners, 3).unwrap();
+            assert!(mapped.is_mapped());
+            assert_eq!(mapped.is_reduced(), loaded.is_reduced());
+
+            for index in 0..loaded.subset().size() {
+                assert_eq!(mapped.get_entry(index), loaded.get_entry(index));
+            }
         }
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(reduced_path).unwrap();
+    }
+
+    // mapping doesn't check the checksum, but the total of the rank blocks still has to match
+    #[test]
+    fn rejects_mapped_reduced_pdbs_with_the_wrong_total() {
+        let (path, pdb) = save_small_corners("wrong_total");
+        let reduced = reduce_pdb(&pdb, SMALL_CORNERS, select_corners, 3, 1);
+        save_reduced_pdb(&path, &reduced, SMALL_CORNERS, select_corners, 3).unwrap();
+
+        let header = read_pdb_header(&path).unwrap();
+        let size = pdb_subset(SMALL_CORNERS, select_corners, 3).size();
+        let total = HEADER_LEN + bitmap_len(size) + ranks_len(size) - 8;
+
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[total] ^= 0x01;
+        fs::write(&path, &bytes).unwrap();
+
+        assert_eq!(
+            file_error(PDB::map(&path, SMALL_CORNERS, select_corners, 3)),
+            PDBFileError::WrongClassCount(header.entries)
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_mapped_reduced_pdbs_with_corrupt_rank_blocks() {
+        let (path, pdb) = save_small_corners("bad_rank_block");
+        let reduced = reduce_pdb(&pdb, SMALL_CORNERS, select_corners, 3, 1);
+        save_reduced_pdb(&path, &reduced, SMALL_CORNERS, select_corners, 3).unwrap();
+
+        let size = pdb_subset(SMALL_CORNERS, select_corners, 3).size();
+        let block = HEADER_LEN + bitmap_len(size) + 8;
+
+        // a block that is too big would have sent lookups past the end of the entries
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[block + 2] ^= 0x01;
+        fs::write(&path, &bytes).unwrap();
+
+        assert_eq!(
+            file_error(PDB::map(&path, SMALL_CORNERS, select_corners, 3)),
+            PDBFileError::BadRankBlock(1)
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_the_wrong_table() {
+        let (path, _) = save_small_corners("mismatch");
+
+        let error = file_error(PDB::new(&path, SMALL_CORNERS, select_edges, 2));
+        assert!(matches!(
+            error,
+            PDBFileError::Mismatch { expected, found }
+                if expected.kind == PieceKind::Edges && found.kind == PieceKind::Corners
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let (path, pdb) = save_small_corners("truncated");
+        let f = OpenOptions::new().write(true).open(&path).unwrap();
+        f.set_len((HEADER_LEN + pdb.len() - 1) as u64).unwrap();
+
+        assert_eq!(
+            file_error(load_small_corners(&path)),
+            PDBFileError::WrongSize {
+                expected: pdb.len() as u64,
+                found: pdb.len() as u64 - 1,
+            }
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_corrupt_entries() {
+        let (path, pdb) = save_small_corners("corrupt");
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[HEADER_LEN + pdb.len() / 2] ^= 0x01;
+        fs::write(&path, &bytes).unwrap();
+
+        assert!(matches!(
+            file_error(load_small_corners(&path)),
+            PDBFileError::BadChecksum { expected, .. } if expected == checksum(&pdb)
+        ));
+
+        fs::remove_file(path).unwrap();
     }
 
-    true
+    #[test]
+    fn rejects_files_without_the_magic() {
+        let (path, _) = save_small_corners("magic");
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[0] = b'X';
+        fs::write(&path, &bytes).unwrap();
+
+        assert_eq!(
+            file_error(load_small_corners(&path)),
+            PDBFileError::BadMagic
+        );
+
+        // too short to even have a header
+        fs::write(&path, b"HSPDB").unwrap();
+        assert_eq!(
+            file_error(load_small_corners(&path)),
+            PDBFileError::BadMagic
+        );
+
+        fs::remove_file(path).unwrap();
+    }
 }
-*/
@@ -0,0 +1,200 @@
+use std::env;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::heuristic::MaxHeuristic;
+use crate::pdb::{pdb_specs, EdgeTables, PDBSpec, PDB};
+
+// Environment variable that can point at the directory holding the PDBs
+pub const PDB_DIR_ENV: &str = "HALFSCRAMBLE_PDB_DIR";
+
+// The command to build a table (or "all") for a set of edge tables into dir
+//  the directory is always given, so the tables end up where the solver looked for them
+//  even when that came from --pdb-dir or HALFSCRAMBLE_PDB_DIR
+pub fn build_command(edges: EdgeTables, table: &str, dir: &Path) -> String {
+    let command = format!(
+        "cargo run --release --bin build_pdb {} {}",
+        table,
+        shell_quote(&dir.to_string_lossy())
+    );
+    match edges {
+        EdgeTables::Six => command,
+        _ => format!("{} --edges {}", command, edges.size()),
+    }
+}
+
+// Quote an argument for the shell if it needs it
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "/._-~+=:,@".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+// Figure out which directory the PDBs live in
+//  1. the directory that was passed in (e.g. from a command line flag)
+//  2. the HALFSCRAMBLE_PDB_DIR environment variable
+//  3. ./data, if it exists (running from the repo root)
+//  4. the XDG data directory ($XDG_DATA_HOME/HalfScramble or ~/.local/share/HalfScramble)
+pub fn resolve_pdb_dir(dir: Option<&Path>) -> PathBuf {
+    if let Some(dir) = dir {
+        return dir.to_path_buf();
+    }
+
+    if let Some(dir) = env::var_os(PDB_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+
+    let local = PathBuf::from("data");
+    if local.is_dir() {
+        return local;
+    }
+
+    xdg_data_dir().unwrap_or(local)
+}
+
+fn xdg_data_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+
+    Some(base.join("HalfScramble"))
+}
+
+#[derive(Debug)]
+pub enum PDBSetError {
+    // one or more of the files don't exist
+    Missing {
+        dir: PathBuf,
+        edges: EdgeTables,
+        files: Vec<usize>,
+    },
+    // the file exists but could not be read
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+}
+
+impl fmt::Display for PDBSetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PDBSetError::Missing { dir, edges, files } => {
+                let specs = pdb_specs(*edges);
+                write!(f, "missing PDBs in '{}':", dir.display())?;
+                for &i in files {
+                    write!(
+                        f,
+                        "\n  {} ({}), rebuild it with `{}`",
+                        specs[i].name,
+                        specs[i].file_name,
+                        build_command(*edges, &(i + 1).to_string(), dir)
+                    )?;
+                }
+                write!(
+                    f,
+                    "\nor build all of them with `{}`",
+                    build_command(*edges, "all", dir)
+                )
+            }
+            PDBSetError::Io { path, error } => {
+                write!(f, "could not load '{}': {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PDBSetError {}
+
+// All of the PDBs the solver needs, loaded from one directory
+pub struct PDBSet {
+    pub dir: PathBuf,
+    pub edges: EdgeTables,
+    pub pdbs: [PDB; 3],
+}
+
+impl PDBSet {
+    // Load the corner and 6-edge tables from dir, memory mapping them where possible
+    //  if any are missing, all of the missing ones are reported at once
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<PDBSet, PDBSetError> {
+        PDBSet::load_with(dir, EdgeTables::Six, true)
+    }
+
+    // Same as load, but with a choice of edge tables
+    //  and mmap can be turned off to read the tables into memory instead
+    pub fn load_with<P: AsRef<Path>>(
+        dir: P,
+        edges: EdgeTables,
+        mmap: bool,
+    ) -> Result<PDBSet, PDBSetError> {
+        let dir = dir.as_ref().to_path_buf();
+        let specs = pdb_specs(edges);
+
+        let missing: Vec<usize> = (0..specs.len())
+            .filter(|&i| !dir.join(specs[i].file_name).is_file())
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(PDBSetError::Missing {
+                dir,
+                edges,
+                files: missing,
+            });
+        }
+
+        let load = |spec: &PDBSpec| -> Result<PDB, PDBSetError> {
+            let path = dir.join(spec.file_name);
+            let open = if mmap { PDB::map } else { PDB::new };
+            open(
+                &path,
+                spec.range.clone(),
+                spec.selector,
+                spec.orientation_base,
+            )
+            .map_err(|error| PDBSetError::Io { path, error })
+        };
+
+        let pdbs: [PDB; 3] = [load(&specs[0])?, load(&specs[1])?, load(&specs[2])?];
+
+        Ok(PDBSet { dir, edges, pdbs })
+    }
+
+    // The heuristic the solver uses, the largest estimate of any of the tables
+    pub fn heuristic(&self) -> MaxHeuristic<'_, PDB> {
+        MaxHeuristic(&self.pdbs)
+    }
+
+    pub fn specs(&self) -> [PDBSpec; 3] {
+        pdb_specs(self.edges)
+    }
+
+    // the path each table was loaded from
+    pub fn path(&self, i: usize) -> PathBuf {
+        self.dir.join(self.specs()[i].file_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_command_writes_to_the_pdb_dir() {
+        assert_eq!(
+            build_command(EdgeTables::Six, "all", Path::new("/tmp/pdbs")),
+            "cargo run --release --bin build_pdb all /tmp/pdbs"
+        );
+        assert_eq!(
+            build_command(EdgeTables::Seven, "2", Path::new("my pdbs")),
+            "cargo run --release --bin build_pdb 2 'my pdbs' --edges 7"
+        );
+        assert_eq!(
+            build_command(EdgeTables::Six, "1", Path::new("it's")),
+            "cargo run --release --bin build_pdb 1 'it'\\''s'"
+        );
+    }
+}
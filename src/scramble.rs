@@ -1,13 +1,23 @@
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-use crate::cube::{Face, Move};
-use crate::pdb::ALL_MOVES;
+use crate::cube::{Move, ALL_MOVES};
+use crate::notation::format_path;
 use crate::solver::OPPOSITE_FACES;
 
 // generates a random scramble of length = len
 pub fn generate_scramble(len: i32) -> Vec<Move> {
-    let mut rng = rand::rng();
+    generate_scramble_with_rng(len, &mut rand::rng())
+}
+
+// generates the same scramble every time for a given seed
+//  so a scramble can be shared by just sharing the seed
+pub fn generate_seeded_scramble(len: i32, seed: u64) -> Vec<Move> {
+    generate_scramble_with_rng(len, &mut StdRng::seed_from_u64(seed))
+}
 
+// generates a random scramble of length = len using the given rng
+pub fn generate_scramble_with_rng<R: Rng + ?Sized>(len: i32, rng: &mut R) -> Vec<Move> {
     let mut scramble: Vec<Move> = Vec::new();
 
     for i in 0..len {
@@ -52,27 +62,6 @@ pub fn invert_path(path: &[Move]) -> Vec<Move> {
     reversed_path
 }
 
-fn print_move(mv: &Move) {
-    match mv.face {
-        Face::U => print!("U"),
-        Face::F => print!("F"),
-        Face::R => print!("R"),
-        Face::L => print!("L"),
-        Face::B => print!("B"),
-        Face::D => print!("D"),
-    }
-
-    match mv.coeff {
-        -1 => print!("`"),
-        2 => print!("2"),
-        _ => (),
-    }
-}
-
 pub fn print_path(path: &[Move]) {
-    for mv in path {
-        print_move(mv);
-        print!(" ");
-    }
-    println!();
+    println!("{}", format_path(path));
 }
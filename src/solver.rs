@@ -1,146 +1,1175 @@
-use crate::pdb::{get_max_heuristic, PDB};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::heuristic::Heuristic;
 
 use crate::cube::{Cube, Face, Move};
 
+// How long the path the solver finds has to be
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LengthPolicy {
+    // exactly this many moves
+    Exact(i32),
+    // the shortest path with at least this many moves
+    AtLeast(i32),
+    // the shortest path there is
+    Optimal,
+    // the shortest path with between min and max moves (inclusive)
+    Range(i32, i32),
+}
+
+// The longest path the solver searches for
+//  the search recurses once per move, so this keeps the stack bounded
+//  (any cube can be solved in 20 moves, so only very long exact lengths are cut off)
+pub const MAX_PATH_LENGTH: i32 = 100;
+
+impl LengthPolicy {
+    // the fewest moves a path can have
+    pub fn min(self) -> i32 {
+        match self {
+            LengthPolicy::Exact(len) | LengthPolicy::AtLeast(len) => len,
+            LengthPolicy::Optimal => 0,
+            LengthPolicy::Range(min, _) => min,
+        }
+    }
+
+    // the most moves a path can have
+    pub fn max(self) -> i32 {
+        match self {
+            LengthPolicy::Exact(len) => len,
+            LengthPolicy::AtLeast(_) | LengthPolicy::Optimal => i32::MAX,
+            LengthPolicy::Range(_, max) => max,
+        }
+    }
+}
+
+// How the path the solver finds has to be different from another path
+//  (the inverse of the scramble, so the alternate doesn't give the solution away)
+#[derive(Clone, Debug)]
+pub struct Constraints {
+    // the path to be different from, going from the scrambled cube to solved
+    pub avoid: Vec<Move>,
+    // the first move of avoid can't be in the first run of moves on the same axis
+    //  (those can be done in any order, so U D starts with D just as much as D U does)
+    pub different_first_move: bool,
+    // the path can't go through any state avoid goes through
+    //  (other than the scrambled and solved cubes at the ends)
+    pub no_shared_states: bool,
+    // the edit distance between the path and avoid has to be at least this
+    //  (both are compared in canonical form, so U D and D U are the same)
+    pub min_edit_distance: usize,
+    // the path can't end with the same moves as the last this many moves of avoid
+    //  (0 allows any suffix)
+    pub no_shared_suffix: usize,
+}
+
+impl Constraints {
+    // Just a different first move
+    pub fn new(avoid: &[Move]) -> Constraints {
+        Constraints {
+            avoid: avoid.to_vec(),
+            different_first_move: true,
+            no_shared_states: false,
+            min_edit_distance: 0,
+            no_shared_suffix: 0,
+        }
+    }
+
+    // No constraints at all, any path will do
+    pub fn none() -> Constraints {
+        Constraints {
+            different_first_move: false,
+            ..Constraints::new(&[])
+        }
+    }
+}
+
+// The constraints, set up for a search from a certain cube
+struct ConstraintCheck<'a> {
+    constraints: &'a Constraints,
+    avoid_canonical: Vec<Move>,
+    // the states avoid goes through, not counting the ends
+    avoid_states: Vec<Cube>,
+}
+
+impl<'a> ConstraintCheck<'a> {
+    fn new(cube: &Cube, constraints: &'a Constraints) -> ConstraintCheck<'a> {
+        let mut avoid_states = Vec::new();
+        if constraints.no_shared_states {
+            let mut state = *cube;
+            for mv in constraints
+                .avoid
+                .iter()
+                .take(constraints.avoid.len().saturating_sub(1))
+            {
+                state.make_move(*mv);
+                avoid_states.push(state);
+            }
+        }
+
+        ConstraintCheck {
+            constraints,
+            avoid_canonical: canonical_form(&constraints.avoid),
+            avoid_states,
+        }
+    }
+
+    // true if the move can be made after the path so far
+    fn allows_move(&self, path: &[Move], mv: Move) -> bool {
+        !(self.constraints.different_first_move
+            && self.constraints.avoid.first() == Some(&mv)
+            && path.iter().all(|prev| axis(prev.face) == axis(mv.face)))
+    }
+
+    // true if the path can go through this state
+    fn allows_state(&self, cube: &Cube) -> bool {
+        !self.avoid_states.contains(cube)
+    }
+
+    // true if a path that solves the cube is different enough from avoid
+    //  it is never avoid with the moves on opposite faces swapped (e.g. D U for U D),
+    //  since that gives avoid away just the same
+    fn accepts(&self, path: &[Move]) -> bool {
+        let constraints = self.constraints;
+        let canonical = canonical_form(path);
+
+        if !constraints.avoid.is_empty() && canonical == self.avoid_canonical {
+            return false;
+        }
+
+        let k = constraints.no_shared_suffix;
+        if k > 0
+            && canonical.len() >= k
+            && self.avoid_canonical.len() >= k
+            && canonical[canonical.len() - k..]
+                == self.avoid_canonical[self.avoid_canonical.len() - k..]
+        {
+            return false;
+        }
+
+        edit_distance(&canonical, &self.avoid_canonical) >= constraints.min_edit_distance
+    }
+}
+
+// Lets a search be stopped from somewhere else (e.g. another thread)
+//  clones share the same flag, so keep one and give one to the Budget
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Counts the nodes (cubes) searches visit, e.g. to see how much of a budget is left
+//  clones share the same count, so keep one and give one to the Budget
+#[derive(Clone, Debug, Default)]
+pub struct NodeCounter(Arc<AtomicU64>);
+
+impl NodeCounter {
+    pub fn new() -> NodeCounter {
+        NodeCounter::default()
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// How much searching the solver can do before it gives up
+//  the default has no limits, like the solver always had
+#[derive(Clone, Debug, Default)]
+pub struct Budget {
+    // the most nodes (cubes) the search can visit
+    pub max_nodes: Option<u64>,
+    // the most time the search can take, from when it starts
+    pub time_limit: Option<Duration>,
+    pub cancel: Option<CancelToken>,
+    // the nodes the search visits are added to this (it doesn't limit anything)
+    pub counter: Option<NodeCounter>,
+}
+
+// Why a search gave up
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StopReason {
+    NodeLimit,
+    TimeLimit,
+    Cancelled,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::NodeLimit => write!(f, "node limit reached"),
+            StopReason::TimeLimit => write!(f, "time limit reached"),
+            StopReason::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+// What the solver came up with
+#[derive(Clone, PartialEq, Debug)]
+pub enum SolveResult {
+    Found(Vec<Move>),
+    // gave up before finding a path, there may or may not be one
+    BudgetExceeded(StopReason),
+    // every path that fits the length and constraints was ruled out
+    NoSolution,
+}
+
+impl SolveResult {
+    // the path, if one was found
+    pub fn found(self) -> Option<Vec<Move>> {
+        match self {
+            SolveResult::Found(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+// What happened during one iteration of IDA* (one threshold)
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct IterationStats {
+    pub threshold: i32,
+    // nodes that had their moves tried
+    pub expanded: u64,
+    // nodes cut off because their estimate was over the threshold
+    pub pruned: u64,
+    // for each part of the heuristic (see Heuristic::components, e.g. each PDB),
+    //  how many nodes it gave the highest estimate for (if they tie, they all count)
+    //  a part that hardly ever wins isn't doing much
+    pub heuristic_wins: Vec<u64>,
+    pub elapsed: Duration,
+}
+
+impl IterationStats {
+    fn new(threshold: i32, num_components: usize) -> IterationStats {
+        IterationStats {
+            threshold,
+            heuristic_wins: vec![0; num_components],
+            ..IterationStats::default()
+        }
+    }
+
+    // Add the counts from another thread's part of the iteration
+    fn add(&mut self, other: &IterationStats) {
+        self.expanded += other.expanded;
+        self.pruned += other.pruned;
+        for (wins, other_wins) in self.heuristic_wins.iter_mut().zip(&other.heuristic_wins) {
+            *wins += other_wins;
+        }
+    }
+}
+
+// Every iteration of a search, in order
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SolveStats {
+    pub iterations: Vec<IterationStats>,
+}
+
+impl SolveStats {
+    pub fn expanded(&self) -> u64 {
+        self.iterations.iter().map(|it| it.expanded).sum()
+    }
+
+    pub fn pruned(&self) -> u64 {
+        self.iterations.iter().map(|it| it.pruned).sum()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.iterations.iter().map(|it| it.elapsed).sum()
+    }
+}
+
+// Keeps track of how much of a budget a search has used
+//  it is shared by every thread of the search, which each count their own nodes
+//  and add them to the total every BUDGET_CHECK_INTERVAL nodes
+struct BudgetTracker {
+    budget: Budget,
+    start: Instant,
+    nodes: AtomicU64,
+    stopped: OnceLock<StopReason>,
+}
+
+// The clock and the cancel flag are only checked this often (in nodes)
+//  since checking them is much slower than visiting a node
+const BUDGET_CHECK_INTERVAL: u64 = 1024;
+
+impl BudgetTracker {
+    fn new(budget: Budget) -> BudgetTracker {
+        BudgetTracker {
+            budget,
+            start: Instant::now(),
+            nodes: AtomicU64::new(0),
+            stopped: OnceLock::new(),
+        }
+    }
+
+    // why the search stopped, if it ran out of budget
+    fn stopped(&self) -> Option<StopReason> {
+        self.stopped.get().copied()
+    }
+
+    // Count a node, returns false if the search has to stop
+    //  unchecked: the nodes this thread has visited that aren't in the total yet
+    fn visit(&self, unchecked: &Cell<u64>) -> bool {
+        if self.stopped.get().is_some() {
+            return false;
+        }
+
+        let count = unchecked.get() + 1;
+        unchecked.set(count);
+
+        let stop = if self
+            .budget
+            .max_nodes
+            .is_some_and(|max| self.nodes.load(Ordering::Relaxed) + count > max)
+        {
+            Some(StopReason::NodeLimit)
+        } else if count < BUDGET_CHECK_INTERVAL {
+            None
+        } else {
+            self.flush(unchecked);
+
+            if self
+                .budget
+                .cancel
+                .as_ref()
+                .is_some_and(|c| c.is_cancelled())
+            {
+                Some(StopReason::Cancelled)
+            } else if self
+                .budget
+                .time_limit
+                .is_some_and(|limit| self.start.elapsed() > limit)
+            {
+                Some(StopReason::TimeLimit)
+            } else {
+                None
+            }
+        };
+
+        if let Some(reason) = stop {
+            let _ = self.stopped.set(reason);
+        }
+        stop.is_none()
+    }
+
+    // Add a thread's unchecked nodes to the total (and the budget's counter)
+    fn flush(&self, unchecked: &Cell<u64>) {
+        let count = unchecked.replace(0);
+        self.nodes.fetch_add(count, Ordering::Relaxed);
+        if let Some(counter) = &self.budget.counter {
+            counter.0.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+}
+
+// The number of moves that have to be added, removed or changed to turn one path into another
+//  (Levenshtein distance)
+pub fn edit_distance(a: &[Move], b: &[Move]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut row = vec![0; b.len() + 1];
+
+    for (i, x) in a.iter().enumerate() {
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let change = prev[j] + (x != y) as usize;
+            row[j + 1] = change.min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+
+    prev[b.len()]
+}
+
 // Finds a different path to the solved cube from the scrambled state
-pub fn solve(
+//  constraints: how it has to be different from the real solution
+//  heuristic: usually the PDBs (see heuristic::MaxHeuristic)
+//  length: how long the path has to be, returns NoSolution if there is no path that fits
+//  budget: how much searching it can do before returning BudgetExceeded
+//  threads: how many threads to search with
+//
+// Some lengths can't be reached (e.g. no path of 1 move solves a solved cube),
+//  so Exact can fail even when a longer path exists.
+// The path found is the same no matter how many threads are used.
+pub fn solve<H: Heuristic + ?Sized>(
+    cube: &Cube,
+    constraints: &Constraints,
+    heuristic: &H,
+    length: LengthPolicy,
+    budget: &Budget,
+    threads: usize,
+) -> SolveResult {
+    solve_inner(cube, constraints, heuristic, length, budget, threads, None)
+}
+
+// Same as solve, but also returns stats on every iteration of the search
+//  collecting them makes the search slower, since every part of the heuristic is looked at
+pub fn solve_with_stats<H: Heuristic + ?Sized>(
     cube: &Cube,
-    last_move_inv: Move,
-    pdb: &[PDB; 3],
-    scramble_len: i32,
-) -> Option<Vec<Move>> {
+    constraints: &Constraints,
+    heuristic: &H,
+    length: LengthPolicy,
+    budget: &Budget,
+    threads: usize,
+) -> (SolveResult, SolveStats) {
+    let mut stats = SolveStats::default();
+    let result = solve_inner(
+        cube,
+        constraints,
+        heuristic,
+        length,
+        budget,
+        threads,
+        Some(&mut stats),
+    );
+    (result, stats)
+}
+
+fn solve_inner<H: Heuristic + ?Sized>(
+    cube: &Cube,
+    constraints: &Constraints,
+    heuristic: &H,
+    length: LengthPolicy,
+    budget: &Budget,
+    threads: usize,
+    mut stats: Option<&mut SolveStats>,
+) -> SolveResult {
+    let (min_len, max_len) = (length.min(), length.max().min(MAX_PATH_LENGTH));
+    if min_len > max_len {
+        return SolveResult::NoSolution;
+    }
+
+    let context = SearchContext {
+        heuristic,
+        constraints: ConstraintCheck::new(cube, constraints),
+        min_len,
+        budget: BudgetTracker::new(budget.clone()),
+        collect_stats: stats.is_some(),
+    };
+
     // The threshold is the minimum number of moves a solution will take (estimate)
     //  all paths with an expected path shorter than this are discarded
-    let mut threshold = heuristic(cube, pdb).max(scramble_len);
-
-    // println!("Heuristic = {}", heuristic(&cube, pdb));
+    let mut threshold = heuristic.estimate(cube).max(min_len);
 
     // Start the recursion
     let mut path: Vec<Move> = Vec::new();
-    loop {
-        let t = search(
-            &cube,
-            0,
-            threshold,
-            &mut path,
-            &last_move_inv,
-            pdb,
-            scramble_len,
-        );
+    while threshold <= max_len {
+        let start = Instant::now();
+        let mut iteration = context
+            .collect_stats
+            .then(|| IterationStats::new(threshold, heuristic.num_components()));
+
+        let t = if threads > 1 {
+            search_parallel(
+                &context,
+                cube,
+                threshold,
+                threads,
+                &mut path,
+                &mut iteration,
+            )
+        } else {
+            let found = AtomicUsize::new(NOT_FOUND);
+            let search = Search::new(&context, &found);
+            let t = search.search(cube, 0, threshold, &mut path);
+            add_stats(&mut iteration, search.finish());
+            t
+        };
+
+        if let (Some(stats), Some(mut iteration)) = (stats.as_deref_mut(), iteration) {
+            iteration.elapsed = start.elapsed();
+            stats.iterations.push(iteration);
+        }
 
-        // if t = -1, path was found, if t = i32::MAX, there is no solution
-        if t == -1 {
-            return Some(path);
+        // if t = FOUND, path was found, if t = i32::MAX, there is no solution
+        if t == FOUND {
+            return SolveResult::Found(path);
+        }
+        if t == STOPPED {
+            // the tracker always has a reason when the search stops
+            return SolveResult::BudgetExceeded(context.budget.stopped().unwrap());
         }
         if t == i32::MAX {
-            return None;
+            return SolveResult::NoSolution;
         }
 
         // increase threshold to t if path is not found
         threshold = t;
-        // println!("Threshold: {}", threshold);
     }
+
+    // every path left is too long
+    SolveResult::NoSolution
+}
+
+// One iteration of IDA*, split between threads
+//  The tree is cut SPLIT_DEPTH moves in, and the threads take the subtrees in order.
+//  Once a path is found, the subtrees after it are given up on, but the ones before it
+//  still finish, so the path returned is from the first subtree that has one,
+//  which is the path a single thread would find.
+fn search_parallel<H: Heuristic + ?Sized>(
+    context: &SearchContext<H>,
+    cube: &Cube,
+    threshold: i32,
+    threads: usize,
+    path: &mut Vec<Move>,
+    stats: &mut Option<IterationStats>,
+) -> i32 {
+    // the index of the first subtree with a path so far
+    let found = AtomicUsize::new(NOT_FOUND);
+
+    let mut subtrees = Vec::new();
+    let root = Search::new(context, &found);
+    let frontier_cost = root.frontier(
+        cube,
+        0,
+        threshold,
+        &mut Vec::new(),
+        SPLIT_DEPTH,
+        &mut subtrees,
+    );
+    add_stats(stats, root.finish());
+
+    if frontier_cost == STOPPED {
+        return STOPPED;
+    }
+
+    // the next subtree for a thread to take
+    let next = AtomicUsize::new(0);
+
+    // every thread returns its lowest cutoff cost, the first path it found and its stats
+    type ThreadResult = (i32, Option<(usize, Vec<Move>)>, Option<IterationStats>);
+    let results: Vec<ThreadResult> = thread::scope(|s| {
+        let handles: Vec<_> = (0..threads.min(subtrees.len()))
+            .map(|_| {
+                s.spawn(|| {
+                    let search = Search::new(context, &found);
+                    let mut min_cost = i32::MAX;
+                    let mut first: Option<(usize, Vec<Move>)> = None;
+
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= subtrees.len() || i > found.load(Ordering::Relaxed) {
+                            break;
+                        }
+
+                        let (node, subtree_path) = &subtrees[i];
+                        let mut subtree_path = subtree_path.clone();
+                        let g = subtree_path.len() as i32;
+
+                        search.subtree.set(i);
+                        let t = search.search(node, g, threshold, &mut subtree_path);
+
+                        if t == FOUND {
+                            found.fetch_min(i, Ordering::Relaxed);
+                            // a thread takes subtrees in order, so its first path is its best
+                            if first.is_none() {
+                                first = Some((i, subtree_path));
+                            }
+                        } else if t == STOPPED {
+                            // either the budget ran out, or an earlier subtree has a path
+                            if context.budget.stopped().is_some() {
+                                break;
+                            }
+                        } else if t < min_cost {
+                            min_cost = t;
+                        }
+                    }
+
+                    (min_cost, first, search.finish())
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("solver thread panicked"))
+            .collect()
+    });
+
+    let mut min_cost = frontier_cost;
+    let mut first: Option<(usize, Vec<Move>)> = None;
+    for (cost, found, thread_stats) in results {
+        min_cost = min_cost.min(cost);
+        add_stats(stats, thread_stats);
+        if let Some((i, found_path)) = found {
+            if first.as_ref().is_none_or(|(j, _)| i < *j) {
+                first = Some((i, found_path));
+            }
+        }
+    }
+
+    if let Some((_, found_path)) = first {
+        *path = found_path;
+        return FOUND;
+    }
+    if context.budget.stopped().is_some() {
+        return STOPPED;
+    }
+    min_cost
 }
 
 const FACES: [Face; 6] = [Face::U, Face::R, Face::F, Face::L, Face::B, Face::D];
 
 pub const OPPOSITE_FACES: [Face; 6] = [Face::D, Face::L, Face::B, Face::R, Face::F, Face::U];
 
-// helper function for the solver (IDA*)
-fn search(
-    node: &Cube,
-    g: i32,
-    threshold: i32,
-    path: &mut Vec<Move>,
-    last_move_inv: &Move,
-    pdbs: &[PDB],
-    scramble_len: i32,
-) -> i32 {
-    // calculate the heuristic using the PDBs
-    let h = heuristic(&node, pdbs);
+// What search returns instead of a cost when it found a path, or had to stop
+const FOUND: i32 = -1;
+const STOPPED: i32 = -2;
+
+// How many moves in the tree is split between threads
+//  2 moves gives a few hundred subtrees, enough to keep the threads busy
+//  even though some subtrees are much bigger than others
+const SPLIT_DEPTH: i32 = 2;
+
+// found when no subtree has a path yet
+const NOT_FOUND: usize = usize::MAX;
+
+// Add a thread's stats to the iteration's, if they are being collected
+fn add_stats(total: &mut Option<IterationStats>, stats: Option<IterationStats>) {
+    if let (Some(total), Some(stats)) = (total, stats) {
+        total.add(&stats);
+    }
+}
+
+// Everything that stays the same during a search, shared between its threads
+struct SearchContext<'a, H: Heuristic + ?Sized> {
+    heuristic: &'a H,
+    constraints: ConstraintCheck<'a>,
+    min_len: i32,
+    budget: BudgetTracker,
+    collect_stats: bool,
+}
 
-    // Total estimated cost (guaranteed not be less than scramble length)
-    let f = (g + h).max(scramble_len);
+// One thread's search
+struct Search<'a, H: Heuristic + ?Sized> {
+    context: &'a SearchContext<'a, H>,
+    // the index of the first subtree with a path (see search_parallel)
+    found: &'a AtomicUsize,
+    // the index of the subtree being searched
+    subtree: Cell<usize>,
+    // nodes that haven't been added to the budget's total yet
+    unchecked: Cell<u64>,
+    // only kept if the context is collecting stats (the threshold and time are left for solve)
+    stats: Option<RefCell<IterationStats>>,
+    // where the parts of the heuristic go, to count their wins
+    components: RefCell<Vec<i32>>,
+}
+
+impl<'a, H: Heuristic + ?Sized> Search<'a, H> {
+    fn new(context: &'a SearchContext<'a, H>, found: &'a AtomicUsize) -> Self {
+        Search {
+            context,
+            found,
+            subtree: Cell::new(0),
+            unchecked: Cell::new(0),
+            stats: context
+                .collect_stats
+                .then(|| RefCell::new(IterationStats::new(0, context.heuristic.num_components()))),
+            components: RefCell::new(Vec::new()),
+        }
+    }
 
-    // If the estimate exceeds the threshold then prune
-    if f > threshold {
-        return f;
+    // Count a node, returns false if the search has to stop
+    fn visit(&self) -> bool {
+        self.context.budget.visit(&self.unchecked)
+            // only check for an earlier path when the budget is checked, it's just as slow
+            && (self.unchecked.get() != 0 || self.found.load(Ordering::Relaxed) >= self.subtree.get())
     }
 
-    // If solved, return to top
-    if node.is_solved() {
-        return -1;
+    // Add the nodes this thread visited to the budget's total, once it's done
+    //  and return its stats
+    fn finish(self) -> Option<IterationStats> {
+        self.context.budget.flush(&self.unchecked);
+        self.stats.map(RefCell::into_inner)
+    }
+
+    fn count(&self, update: impl FnOnce(&mut IterationStats)) {
+        if let Some(stats) = &self.stats {
+            update(&mut stats.borrow_mut());
+        }
     }
 
-    // initalize the min cost as "infinity"
-    let mut min_cost: i32 = i32::MAX;
+    // The heuristic's estimate for a node, counting which of its parts won for the stats
+    fn estimate(&self, node: &Cube) -> i32 {
+        if let Some(stats) = &self.stats {
+            let mut components = self.components.borrow_mut();
+            components.clear();
+            self.context.heuristic.components(node, &mut components);
+
+            let best = components.iter().copied().max();
+            let mut stats = stats.borrow_mut();
+            for (wins, &h) in stats.heuristic_wins.iter_mut().zip(components.iter()) {
+                if Some(h) == best {
+                    *wins += 1;
+                }
+            }
+        }
+
+        self.context.heuristic.estimate(node)
+    }
 
-    // Check all moves
-    for &face in FACES.iter() {
+    // true if search tries the move after the path so far
+    fn allows_move(&self, path: &[Move], mv: Move) -> bool {
         // Prevent redundant moves
         if let Some(prev) = path.last() {
             // Stop from moving the same face twice
-            if face == prev.face {
-                continue;
+            if mv.face == prev.face {
+                return false;
             }
 
             // Stop from moving the same axis 3 times
             //  e.g. (F B F)
-            if let Some(prevprev) = path.get(path.len() - 2) {
-                if face == prevprev.face && OPPOSITE_FACES[face as usize] == prev.face {
+            if let Some(prevprev) = path.iter().rev().nth(1) {
+                if mv.face == prevprev.face && OPPOSITE_FACES[mv.face as usize] == prev.face {
+                    return false;
+                }
+            }
+        }
+
+        // e.g. prevent first move being inverse of scramble
+        self.context.constraints.allows_move(path, mv)
+    }
+
+    // helper function for the solver (IDA*)
+    fn search(&self, node: &Cube, g: i32, threshold: i32, path: &mut Vec<Move>) -> i32 {
+        if !self.visit() {
+            return STOPPED;
+        }
+
+        // calculate the heuristic (usually from the PDBs)
+        let h = self.estimate(node);
+
+        // Total estimated cost (guaranteed not be less than the minimum length)
+        let f = (g + h).max(self.context.min_len);
+
+        // If the estimate exceeds the threshold then prune
+        if f > threshold {
+            self.count(|stats| stats.pruned += 1);
+            return f;
+        }
+
+        // If solved with enough moves (and different enough), return to top
+        //  otherwise the search has to leave and come back
+        if node.is_solved() && g >= self.context.min_len && self.context.constraints.accepts(path) {
+            return FOUND;
+        }
+
+        self.count(|stats| stats.expanded += 1);
+
+        // initalize the min cost as "infinity"
+        let mut min_cost: i32 = i32::MAX;
+
+        // Check all moves
+        for &face in FACES.iter() {
+            // Iterate over coefficients (-1 = CW, 1 = CCW, 2 = Double Turn)
+            for coeff in COEFFS {
+                let mv = Move { face, coeff };
+
+                if !self.allows_move(path, mv) {
+                    continue;
+                }
+
+                // Apply move
+                let mut new_node = *node;
+                new_node.make_move(mv);
+
+                if !self.context.constraints.allows_state(&new_node) {
+                    continue;
+                }
+
+                // Push to path
+                path.push(mv);
+
+                // Recursive search
+                let t = self.search(&new_node, g + 1, threshold, path);
+
+                // If found (or had to stop), go to top
+                if t == FOUND || t == STOPPED {
+                    return t;
+                }
+
+                // Track minimum cutoff cost
+                if t < min_cost {
+                    min_cost = t;
+                }
+
+                // Backtrack
+                path.pop();
+            }
+        }
+
+        min_cost
+    }
+
+    // Collect the nodes depth moves in (and the paths to them) for search_parallel,
+    //  in the order search would get to them
+    //  returns the lowest cost of the nodes pruned on the way, or STOPPED
+    //
+    // The nodes collected aren't looked at here, search does that when it starts on them.
+    //  Solved nodes on the way are collected too, and not gone past,
+    //  since search has to check if they are far enough along first.
+    fn frontier(
+        &self,
+        node: &Cube,
+        g: i32,
+        threshold: i32,
+        path: &mut Vec<Move>,
+        depth: i32,
+        subtrees: &mut Vec<(Cube, Vec<Move>)>,
+    ) -> i32 {
+        if depth == 0 || node.is_solved() {
+            subtrees.push((*node, path.clone()));
+            return i32::MAX;
+        }
+
+        if !self.visit() {
+            return STOPPED;
+        }
+
+        let f = (g + self.estimate(node)).max(self.context.min_len);
+        if f > threshold {
+            self.count(|stats| stats.pruned += 1);
+            return f;
+        }
+
+        self.count(|stats| stats.expanded += 1);
+
+        let mut min_cost: i32 = i32::MAX;
+        for &face in FACES.iter() {
+            for coeff in COEFFS {
+                let mv = Move { face, coeff };
+                if !self.allows_move(path, mv) {
+                    continue;
+                }
+
+                let mut new_node = *node;
+                new_node.make_move(mv);
+                if !self.context.constraints.allows_state(&new_node) {
                     continue;
                 }
+
+                path.push(mv);
+                let t = self.frontier(&new_node, g + 1, threshold, path, depth - 1, subtrees);
+                path.pop();
+
+                if t == STOPPED {
+                    return STOPPED;
+                }
+                min_cost = min_cost.min(t);
             }
         }
 
-        // Iterate over coefficients (-1 = CW, 1 = CCW, 2 = Double Turn)
-        for coeff in [-1, 1, 2] {
-            // Prevent first move being inverse of scramble
-            if path.is_empty() && face == last_move_inv.face && coeff == last_move_inv.coeff {
+        min_cost
+    }
+}
+
+// vvv Enumerating solutions vvv
+//
+// Two paths that only differ in the order of moves on opposite faces (e.g. U D and D U)
+//  do the same thing, so they count as one solution.
+//  The canonical form of a path puts every such pair in the order of FACES,
+//  and only canonical paths are generated, so every solution comes up once.
+
+// true if a move can come after the path so far without it being redundant or non canonical
+fn is_canonical_next(path: &[Move], face: Face) -> bool {
+    match path.last() {
+        None => true,
+        Some(prev) if prev.face == face => false,
+        // opposite faces commute, so only allow them in one order
+        //  (this also rules out moving the same axis 3 times, e.g. F B F)
+        Some(prev) if OPPOSITE_FACES[face as usize] == prev.face => {
+            (prev.face as usize) < (face as usize)
+        }
+        Some(_) => true,
+    }
+}
+
+// the same for a face and its opposite
+fn axis(face: Face) -> usize {
+    (face as usize).min(OPPOSITE_FACES[face as usize] as usize)
+}
+
+// Sort every run of moves on the same axis into the order of FACES
+//  paths with the same canonical form are the same solution
+pub fn canonical_form(path: &[Move]) -> Vec<Move> {
+    let mut canonical = path.to_vec();
+    let mut start = 0;
+    while start < canonical.len() {
+        let run_axis = axis(canonical[start].face);
+        let mut end = start + 1;
+        while end < canonical.len() && axis(canonical[end].face) == run_axis {
+            end += 1;
+        }
+
+        canonical[start..end].sort_by_key(|mv| mv.face as usize);
+        start = end;
+    }
+
+    canonical
+}
+
+// An iterator over every solution of an exact length, in canonical form (see above)
+//  Use take(k) for just the first k, or count() to see how many alternate paths there are.
+//  Like solve, every solution meets the constraints.
+//  With a budget, it stops early when that runs out (see stop_reason).
+pub struct Solutions<'a, H: Heuristic + ?Sized> {
+    heuristic: &'a H,
+    constraints: ConstraintCheck<'a>,
+    length: usize,
+    path: Vec<Move>,
+    // the cube after each move of the path, the scrambled cube first
+    cubes: Vec<Cube>,
+    // the next move to try at each depth (an index into FACES x COEFFS)
+    next: Vec<usize>,
+    // the first moves to try, all of them unless the search is split between threads
+    first_moves: std::ops::Range<usize>,
+    done: bool,
+    // shared by every thread when the search is split (see find_solutions)
+    budget: Arc<BudgetTracker>,
+    unchecked: Cell<u64>,
+}
+
+const COEFFS: [i8; 3] = [-1, 1, 2];
+
+impl<'a, H: Heuristic + ?Sized> Solutions<'a, H> {
+    pub fn new(cube: &Cube, constraints: &'a Constraints, heuristic: &'a H, length: i32) -> Self {
+        Solutions::starting_with(
+            cube,
+            constraints,
+            heuristic,
+            length,
+            0..FACES.len() * COEFFS.len(),
+            Arc::new(BudgetTracker::new(Budget::default())),
+        )
+    }
+
+    // Only the solutions whose first move is in first_moves (indices into FACES x COEFFS)
+    fn starting_with(
+        cube: &Cube,
+        constraints: &'a Constraints,
+        heuristic: &'a H,
+        length: i32,
+        first_moves: std::ops::Range<usize>,
+        budget: Arc<BudgetTracker>,
+    ) -> Self {
+        Solutions {
+            heuristic,
+            constraints: ConstraintCheck::new(cube, constraints),
+            length: length.max(0) as usize,
+            path: Vec::new(),
+            cubes: vec![*cube],
+            next: vec![first_moves.start],
+            first_moves,
+            // nothing can be found if the cube is further away than the length
+            done: length < 0 || heuristic.estimate(cube) > length,
+            budget,
+            unchecked: Cell::new(0),
+        }
+    }
+
+    // Limit how much searching it does, from now on
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = Arc::new(BudgetTracker::new(budget));
+        self
+    }
+
+    // Why it stopped early, if it ran out of budget
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.budget.stopped()
+    }
+}
+
+impl<H: Heuristic + ?Sized> Iterator for Solutions<'_, H> {
+    type Item = Vec<Move>;
+
+    fn next(&mut self) -> Option<Vec<Move>> {
+        if self.done {
+            return None;
+        }
+
+        // the empty path is the only solution of length 0
+        if self.length == 0 {
+            self.done = true;
+            return (self.cubes[0].is_solved() && self.constraints.accepts(&[])).then(Vec::new);
+        }
+
+        // depth first search, with the stack kept in self so it can stop at every solution
+        loop {
+            let depth = self.path.len();
+
+            // every move at this depth has been tried, so backtrack
+            let end = if depth == 0 {
+                self.first_moves.end
+            } else {
+                FACES.len() * COEFFS.len()
+            };
+            if self.next[depth] == end {
+                if depth == 0 {
+                    self.done = true;
+                    return None;
+                }
+                self.path.pop();
+                self.cubes.pop();
+                self.next.pop();
                 continue;
             }
 
-            let mv = Move { face, coeff };
+            if !self.budget.visit(&self.unchecked) {
+                self.done = true;
+                return None;
+            }
 
-            // Apply move
-            let mut new_node = node.clone();
-            new_node.make_move(mv);
+            let m = self.next[depth];
+            self.next[depth] += 1;
 
-            // Push to path
-            path.push(mv);
+            let mv = Move {
+                face: FACES[m / COEFFS.len()],
+                coeff: COEFFS[m % COEFFS.len()],
+            };
 
-            // Recursive search
-            let t = search(
-                &new_node,
-                g + 1,
-                threshold,
-                path,
-                last_move_inv,
-                pdbs,
-                scramble_len,
-            );
+            if !is_canonical_next(&self.path, mv.face)
+                || !self.constraints.allows_move(&self.path, mv)
+            {
+                continue;
+            }
 
-            // If found, go to top
-            if t == -1 {
-                return -1;
+            let mut cube = self.cubes[depth];
+            cube.make_move(mv);
+
+            if !self.constraints.allows_state(&cube) {
+                continue;
             }
 
-            // Track minimum cutoff cost
-            if t < min_cost {
-                min_cost = t;
+            // prune if the rest can't be done in the moves that are left
+            let g = depth + 1;
+            if g + self.heuristic.estimate(&cube) as usize > self.length {
+                continue;
+            }
+
+            if g == self.length {
+                if cube.is_solved() {
+                    let mut solution = self.path.clone();
+                    solution.push(mv);
+                    if self.constraints.accepts(&solution) {
+                        return Some(solution);
+                    }
+                }
+                continue;
             }
 
-            // Backtrack
-            path.pop();
+            self.path.push(mv);
+            self.cubes.push(cube);
+            self.next.push(0);
         }
     }
+}
 
-    min_cost
+// Add the nodes that haven't been counted yet to the budget's total,
+//  which is shared with the other threads when the search is split (see find_solutions)
+impl<H: Heuristic + ?Sized> Drop for Solutions<'_, H> {
+    fn drop(&mut self) {
+        self.budget.flush(&self.unchecked);
+    }
+}
+
+// The first 'limit' solutions of Solutions, with the search split between threads
+//  by the first move. The solutions, and their order, are the same however many threads
+//  are used (unless the budget runs out part way through).
+//  The budget is shared by all of the threads.
+//  Also returns why it stopped early, if the budget ran out.
+pub fn find_solutions<H: Heuristic + ?Sized>(
+    cube: &Cube,
+    constraints: &Constraints,
+    heuristic: &H,
+    length: i32,
+    limit: usize,
+    budget: &Budget,
+    threads: usize,
+) -> (Vec<Vec<Move>>, Option<StopReason>) {
+    let tracker = Arc::new(BudgetTracker::new(budget.clone()));
+    let num_moves = FACES.len() * COEFFS.len();
+
+    if threads <= 1 || length <= 0 {
+        let mut solutions = Solutions::starting_with(
+            cube,
+            constraints,
+            heuristic,
+            length,
+            0..num_moves,
+            Arc::clone(&tracker),
+        );
+        let found = solutions.by_ref().take(limit).collect();
+        return (found, tracker.stopped());
+    }
+
+    // the solutions under each first move, None until that subtree has been searched
+    let subtrees: Mutex<Vec<Option<Vec<Vec<Move>>>>> = Mutex::new(vec![None; num_moves]);
+    // the next first move for a thread to take
+    let next = AtomicUsize::new(0);
+
+    thread::scope(|s| {
+        for _ in 0..threads.min(num_moves) {
+            s.spawn(|| loop {
+                let m = next.fetch_add(1, Ordering::Relaxed);
+                if m >= num_moves || has_enough(&subtrees.lock().unwrap()[..m], limit) {
+                    break;
+                }
+
+                let found: Vec<Vec<Move>> = Solutions::starting_with(
+                    cube,
+                    constraints,
+                    heuristic,
+                    length,
+                    m..m + 1,
+                    Arc::clone(&tracker),
+                )
+                .take(limit)
+                .collect();
+
+                subtrees.lock().unwrap()[m] = Some(found);
+            });
+        }
+    });
+
+    let found = subtrees
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .flatten()
+        .take(limit)
+        .collect();
+
+    (found, tracker.stopped())
 }
 
-// calculate the heurstic
-fn heuristic(cube: &Cube, pdbs: &[PDB]) -> i32 {
-    get_max_heuristic(cube, pdbs)
+// true if the subtrees that have been searched so far, up to the first one that hasn't,
+//  already have 'limit' solutions (so the ones after them aren't needed)
+//  the threads take the subtrees in order, so once this is true it stays true
+fn has_enough(subtrees: &[Option<Vec<Vec<Move>>>], limit: usize) -> bool {
+    let mut count = 0;
+    for subtree in subtrees {
+        match subtree {
+            Some(found) => count += found.len(),
+            None => break,
+        }
+    }
+    count >= limit
 }
 
 // very basic heuristic
@@ -164,3 +1193,519 @@ fn computational_heuristic(cube: &Cube) -> i32 {
     misplaced / 4
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Piece;
+    use crate::heuristic::{MaxHeuristic, ZeroHeuristic};
+    use crate::notation::{format_path, parse_path};
+    use crate::scramble::invert_path;
+
+    // A move moves at most 4 corners and 4 edges, so a quarter of the misplaced pieces
+    //  of either kind (rounded up) is never too much, and it is a lot faster than nothing
+    struct MisplacedHeuristic;
+
+    impl Heuristic for MisplacedHeuristic {
+        fn estimate(&self, cube: &Cube) -> i32 {
+            let misplaced = |pieces: &[Piece]| {
+                pieces
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, piece)| piece.pos != *i as i32 || piece.ori != 0)
+                    .count() as i32
+            };
+            (misplaced(&cube.corners).max(misplaced(&cube.edges)) + 3) / 4
+        }
+    }
+
+    // a cube a few moves from solved, and the constraints to find other paths for it
+    fn scrambled(scramble: &str) -> (Cube, Constraints) {
+        let scramble = parse_path(scramble).unwrap();
+        let mut cube = Cube::new();
+        for mv in &scramble {
+            cube.make_move(*mv);
+        }
+
+        (cube, Constraints::new(&invert_path(&scramble)))
+    }
+
+    // MisplacedHeuristic has to be admissible for the other tests to mean anything
+    #[test]
+    fn misplaced_heuristic_finds_optimal_paths() {
+        for scramble in ["R U", "R U2 F'", "F B U2 R'"] {
+            let (cube, _) = scrambled(scramble);
+            let optimal = |heuristic: &dyn Heuristic| {
+                solve(
+                    &cube,
+                    &Constraints::none(),
+                    heuristic,
+                    LengthPolicy::Optimal,
+                    &Budget::default(),
+                    1,
+                )
+                .found()
+                .unwrap()
+                .len()
+            };
+
+            assert_eq!(
+                optimal(&MisplacedHeuristic),
+                optimal(&ZeroHeuristic),
+                "{}",
+                scramble
+            );
+        }
+    }
+
+    fn solves(cube: &Cube, path: &[Move]) -> bool {
+        let mut cube = *cube;
+        for mv in path {
+            cube.make_move(*mv);
+        }
+        cube.is_solved()
+    }
+
+    fn solve_length(cube: &Cube, length: LengthPolicy) -> SolveResult {
+        solve(
+            cube,
+            &Constraints::none(),
+            &MisplacedHeuristic,
+            length,
+            &Budget::default(),
+            1,
+        )
+    }
+
+    // the lengths up to 6 that have a path, from Exact
+    fn exact_lengths(cube: &Cube) -> Vec<usize> {
+        (0..=6)
+            .filter(
+                |&len| match solve_length(cube, LengthPolicy::Exact(len as i32)) {
+                    SolveResult::Found(path) => {
+                        assert_eq!(path.len(), len);
+                        assert!(solves(cube, &path));
+                        true
+                    }
+                    SolveResult::NoSolution => false,
+                    other => panic!("unexpected {:?}", other),
+                },
+            )
+            .collect()
+    }
+
+    #[test]
+    fn exact_finds_exactly_n_moves_or_nothing() {
+        let (cube, _) = scrambled("R2 L2 U2");
+        let lengths = exact_lengths(&cube);
+        assert_eq!(lengths, [3, 5]);
+    }
+
+    #[test]
+    fn at_least_finds_the_shortest_long_enough_path() {
+        let (cube, _) = scrambled("R2 L2 U2");
+        let lengths = exact_lengths(&cube);
+
+        for min in 0..=5 {
+            let found = solve_length(&cube, LengthPolicy::AtLeast(min))
+                .found()
+                .unwrap();
+            assert!(solves(&cube, &found));
+            let shortest = lengths.iter().find(|&&len| len >= min as usize);
+            assert_eq!(Some(&found.len()), shortest, "at least {}", min);
+        }
+    }
+
+    // without the cap, the search would recurse 200000 moves deep and overflow the stack
+    #[test]
+    fn lengths_past_max_path_length_have_no_solution() {
+        let (cube, _) = scrambled("R U");
+        for length in [
+            LengthPolicy::Exact(200_000),
+            LengthPolicy::AtLeast(200_000),
+            LengthPolicy::Range(MAX_PATH_LENGTH + 1, i32::MAX),
+        ] {
+            assert_eq!(
+                solve_length(&cube, length),
+                SolveResult::NoSolution,
+                "{:?}",
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn range_keeps_to_both_bounds() {
+        let (cube, _) = scrambled("R2 L2 U2");
+        let lengths = exact_lengths(&cube);
+
+        for min in 0..=6 {
+            for max in min..=6 {
+                let shortest = lengths
+                    .iter()
+                    .find(|&&len| (min as usize..=max as usize).contains(&len));
+                match solve_length(&cube, LengthPolicy::Range(min, max)) {
+                    SolveResult::Found(path) => {
+                        assert!(solves(&cube, &path));
+                        assert_eq!(Some(&path.len()), shortest, "{}-{}", min, max);
+                    }
+                    SolveResult::NoSolution => assert_eq!(shortest, None, "{}-{}", min, max),
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+        }
+        assert_eq!(
+            solve_length(&cube, LengthPolicy::Range(3, 2)),
+            SolveResult::NoSolution
+        );
+    }
+
+    // the scrambles end in moves on opposite faces, so the real solution starts with them
+    //  (in either order)
+    const COMMUTING_SCRAMBLES: [&str; 4] = ["R U D", "B L R'", "F2 R U' D2", "U2 R2 L2"];
+
+    // solve and Solutions have to agree on which paths are different enough,
+    //  and neither can give the real solution back with opposite faces swapped
+    #[test]
+    fn solve_and_solutions_never_return_the_real_solution() {
+        for scramble in COMMUTING_SCRAMBLES {
+            let (cube, constraints) = scrambled(scramble);
+            let avoid = canonical_form(&constraints.avoid);
+
+            for len in avoid.len()..=avoid.len() + 2 {
+                let all: Vec<Vec<Move>> =
+                    Solutions::new(&cube, &constraints, &MisplacedHeuristic, len as i32).collect();
+                assert!(!all.contains(&avoid), "{}", scramble);
+
+                let found = solve(
+                    &cube,
+                    &constraints,
+                    &MisplacedHeuristic,
+                    LengthPolicy::Exact(len as i32),
+                    &Budget::default(),
+                    1,
+                );
+                match found {
+                    SolveResult::Found(path) => {
+                        assert!(all.contains(&canonical_form(&path)), "{}", scramble)
+                    }
+                    SolveResult::NoSolution => assert!(all.is_empty(), "{}", scramble),
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+        }
+    }
+
+    // every node Solutions visits is counted, even when there are fewer than
+    //  BUDGET_CHECK_INTERVAL of them
+    #[test]
+    fn solutions_count_their_nodes_when_dropped() {
+        let (cube, constraints) = scrambled("R2 L2");
+        let tracker = Arc::new(BudgetTracker::new(Budget::default()));
+
+        let found = Solutions::starting_with(
+            &cube,
+            &constraints,
+            &MisplacedHeuristic,
+            2,
+            0..FACES.len() * COEFFS.len(),
+            Arc::clone(&tracker),
+        )
+        .count();
+
+        assert_eq!(found, 0);
+        assert!(tracker.nodes.load(Ordering::Relaxed) > 0);
+    }
+
+    fn solve_constrained(cube: &Cube, constraints: &Constraints) -> Vec<Move> {
+        solve(
+            cube,
+            constraints,
+            &MisplacedHeuristic,
+            LengthPolicy::AtLeast(constraints.avoid.len() as i32),
+            &Budget::default(),
+            1,
+        )
+        .found()
+        .unwrap()
+    }
+
+    #[test]
+    fn first_move_is_different_in_any_order() {
+        for scramble in COMMUTING_SCRAMBLES {
+            let (cube, constraints) = scrambled(scramble);
+            let path = solve_constrained(&cube, &constraints);
+            assert!(solves(&cube, &path));
+
+            let first_run = path
+                .iter()
+                .take_while(|mv| axis(mv.face) == axis(path[0].face));
+            for mv in first_run {
+                assert_ne!(*mv, constraints.avoid[0], "{}", scramble);
+            }
+        }
+
+        // "R U D" used to give back "R D U"
+        let (cube, constraints) = scrambled("R U D");
+        let path = solve_constrained(&cube, &constraints);
+        assert_ne!(format_path(&invert_path(&path)), "R D U");
+    }
+
+    #[test]
+    fn no_shared_states_avoids_every_state() {
+        let (cube, constraints) = scrambled("R U F");
+        let constraints = Constraints {
+            no_shared_states: true,
+            ..constraints
+        };
+        let path = solve_constrained(&cube, &constraints);
+        assert!(solves(&cube, &path));
+
+        // the states in between, not counting the scrambled and solved cubes
+        let states = |path: &[Move]| {
+            let mut state = cube;
+            let mut states = Vec::new();
+            for mv in &path[..path.len() - 1] {
+                state.make_move(*mv);
+                states.push(state);
+            }
+            states
+        };
+        let avoid_states = states(&constraints.avoid);
+        for state in states(&path) {
+            assert!(!avoid_states.contains(&state));
+        }
+    }
+
+    #[test]
+    fn min_edit_distance_is_kept() {
+        let (cube, constraints) = scrambled("R U F");
+        let constraints = Constraints {
+            min_edit_distance: 3,
+            ..constraints
+        };
+        let path = solve_constrained(&cube, &constraints);
+        assert!(solves(&cube, &path));
+        assert!(edit_distance(&canonical_form(&path), &canonical_form(&constraints.avoid)) >= 3);
+    }
+
+    #[test]
+    fn no_shared_suffix_is_kept() {
+        for k in [1, 2] {
+            let (cube, constraints) = scrambled("R U F");
+            let constraints = Constraints {
+                no_shared_suffix: k,
+                ..constraints
+            };
+            let path = canonical_form(&solve_constrained(&cube, &constraints));
+            let avoid = canonical_form(&constraints.avoid);
+            assert!(solves(&cube, &path));
+            assert_ne!(path[path.len() - k..], avoid[avoid.len() - k..]);
+        }
+    }
+
+    // a search that takes far more than BUDGET_CHECK_INTERVAL nodes
+    fn solve_with_budget(budget: &Budget, threads: usize) -> SolveResult {
+        let (cube, constraints) = scrambled("R U F L B D R2 U2");
+        solve(
+            &cube,
+            &constraints,
+            &ZeroHeuristic,
+            LengthPolicy::AtLeast(8),
+            budget,
+            threads,
+        )
+    }
+
+    #[test]
+    fn solve_stops_when_cancelled() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let budget = Budget {
+            cancel: Some(cancel),
+            ..Budget::default()
+        };
+
+        for threads in [1, 4] {
+            assert_eq!(
+                solve_with_budget(&budget, threads),
+                SolveResult::BudgetExceeded(StopReason::Cancelled)
+            );
+        }
+    }
+
+    #[test]
+    fn solve_stops_at_the_node_limit() {
+        let budget = Budget {
+            max_nodes: Some(5000),
+            ..Budget::default()
+        };
+
+        for threads in [1, 4] {
+            assert_eq!(
+                solve_with_budget(&budget, threads),
+                SolveResult::BudgetExceeded(StopReason::NodeLimit)
+            );
+        }
+    }
+
+    #[test]
+    fn node_counter_adds_up_every_search() {
+        let counter = NodeCounter::new();
+        let budget = Budget {
+            counter: Some(counter.clone()),
+            ..Budget::default()
+        };
+
+        let (cube, constraints) = scrambled("R U F");
+        let search = || {
+            solve(
+                &cube,
+                &constraints,
+                &MisplacedHeuristic,
+                LengthPolicy::AtLeast(3),
+                &budget,
+                1,
+            )
+        };
+
+        assert!(search().found().is_some());
+        let once = counter.get();
+        assert!(once > 0);
+
+        search();
+        assert_eq!(counter.get(), 2 * once);
+    }
+
+    #[test]
+    fn solve_stops_at_the_time_limit() {
+        let budget = Budget {
+            time_limit: Some(Duration::ZERO),
+            ..Budget::default()
+        };
+
+        for threads in [1, 4] {
+            assert_eq!(
+                solve_with_budget(&budget, threads),
+                SolveResult::BudgetExceeded(StopReason::TimeLimit)
+            );
+        }
+    }
+
+    #[test]
+    fn solve_finds_the_same_path_on_any_threads() {
+        // most 3 move scrambles only have alternates of 8 moves, which take too long here,
+        //  so those are solved without constraints
+        let cases = [
+            ("U2 R2 L2", true),
+            ("F2 B2 U", true),
+            ("R U D", false),
+            ("F' L B2", false),
+        ];
+
+        for (scramble, constrained) in cases {
+            let (cube, constraints) = scrambled(scramble);
+            let len = constraints.avoid.len() as i32;
+            let constraints = if constrained {
+                constraints
+            } else {
+                Constraints::none()
+            };
+
+            for length in [
+                LengthPolicy::Exact(len),
+                LengthPolicy::AtLeast(len),
+                LengthPolicy::Optimal,
+                LengthPolicy::Range(len + 1, len + 3),
+            ] {
+                let solve_on = |threads| {
+                    solve(
+                        &cube,
+                        &constraints,
+                        &MisplacedHeuristic,
+                        length,
+                        &Budget::default(),
+                        threads,
+                    )
+                };
+                assert_eq!(solve_on(1), solve_on(4), "{} {:?}", scramble, length);
+            }
+        }
+    }
+
+    #[test]
+    fn stats_follow_the_search() {
+        let parts: [&dyn Heuristic; 2] = [&MisplacedHeuristic, &ZeroHeuristic];
+        let heuristic = MaxHeuristic(&parts);
+        let (cube, constraints) = scrambled("F2 B2 U");
+
+        for threads in [1, 4] {
+            let length = LengthPolicy::AtLeast(3);
+            let budget = Budget::default();
+            let (result, stats) =
+                solve_with_stats(&cube, &constraints, &heuristic, length, &budget, threads);
+
+            assert_eq!(
+                result,
+                solve(&cube, &constraints, &heuristic, length, &budget, threads)
+            );
+            assert!(stats.iterations.len() > 1);
+            assert!(stats.expanded() > 0);
+            for pair in stats.iterations.windows(2) {
+                assert!(pair[0].threshold < pair[1].threshold);
+            }
+            for iteration in &stats.iterations {
+                assert_eq!(iteration.heuristic_wins.len(), heuristic.num_components());
+                // MisplacedHeuristic is never below ZeroHeuristic, so it wins every tie too
+                assert!(iteration.heuristic_wins[0] > 0);
+                assert!(iteration.heuristic_wins[0] >= iteration.heuristic_wins[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn find_solutions_matches_solutions_on_any_threads() {
+        let (cube, constraints) = scrambled("R2 L2");
+        let all: Vec<Vec<Move>> =
+            Solutions::new(&cube, &constraints, &MisplacedHeuristic, 6).collect();
+        assert_eq!(all.len(), 2);
+
+        for limit in [1, 2, 3] {
+            for threads in [1, 2, 4] {
+                let (found, stopped) = find_solutions(
+                    &cube,
+                    &constraints,
+                    &MisplacedHeuristic,
+                    6,
+                    limit,
+                    &Budget::default(),
+                    threads,
+                );
+                assert_eq!(stopped, None);
+                assert_eq!(found, all[..limit.min(all.len())], "{} threads", threads);
+            }
+        }
+    }
+
+    #[test]
+    fn find_solutions_stops_at_the_node_limit() {
+        let (cube, constraints) = scrambled("R2 L2");
+        let budget = Budget {
+            max_nodes: Some(100),
+            ..Budget::default()
+        };
+
+        for threads in [1, 4] {
+            let (_, stopped) = find_solutions(
+                &cube,
+                &constraints,
+                &MisplacedHeuristic,
+                6,
+                1000,
+                &budget,
+                threads,
+            );
+            assert_eq!(stopped, Some(StopReason::NodeLimit));
+        }
+    }
+}